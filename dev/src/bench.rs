@@ -0,0 +1,127 @@
+use ::{
+    anyhow::Context as _,
+    clap::Parser,
+    rofi_unicode::config,
+    std::{env, fmt::Write as _, fs, path::Path, process, time::Duration},
+};
+
+/// Measure how long it takes to read a large configuration.
+///
+/// The configuration is generated into a temporary directory beforehand,
+/// the same every time, so that runs on different machines and checkouts are comparable.
+/// It has a flat list of code points, like `codepoints.ron`,
+/// and nested lists of variations, like `emojis.ron`, both with markup in their names.
+///
+/// The time is split into reading and parsing RON, checking for missing includes
+/// and parsing the markup of names, with the remainder spent building items.
+#[derive(Parser)]
+pub struct Args {
+    /// How many code points the configuration has.
+    #[clap(long, default_value = "150000")]
+    code_points: usize,
+
+    /// How many times to read the configuration.
+    #[clap(long, default_value = "10")]
+    iterations: usize,
+
+    /// Fail if the median time to read the configuration exceeds this many milliseconds.
+    #[clap(long)]
+    max_ms: Option<u64>,
+}
+
+pub(crate) fn bench(
+    Args {
+        code_points,
+        iterations,
+        max_ms,
+    }: Args,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(iterations > 0, "at least one iteration is needed");
+
+    let dir = env::temp_dir().join(format!("rofi-unicode-bench-{}", process::id()));
+    write_fixture(&*dir, code_points)
+        .with_context(|| format!("failed to write the configuration to {}", dir.display()))?;
+    let res = run(&*dir, iterations, max_ms);
+    let _ = fs::remove_dir_all(&*dir);
+    res
+}
+
+fn run(dir: &Path, iterations: usize, max_ms: Option<u64>) -> anyhow::Result<()> {
+    let mut runs = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let (_, timings) = config::read_timed(vec![dir.to_owned()], "config.ron")?;
+        runs.push(timings);
+    }
+    runs.sort_unstable_by_key(|timings| timings.total);
+
+    let median = runs[runs.len() / 2];
+    println!("median of {iterations} runs:");
+    println!("\ttotal: {:?}", median.total);
+    println!("\tparsing RON: {:?}", median.parse);
+    println!("\tchecking includes: {:?}", median.check_includes);
+    println!("\tparsing markup: {:?}", median.markup);
+    println!("fastest run: {:?}", runs[0].total);
+
+    if let Some(max_ms) = max_ms {
+        let max = Duration::from_millis(max_ms);
+        anyhow::ensure!(
+            median.total <= max,
+            "median time {:?} exceeds the maximum of {max:?}",
+            median.total
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the configuration that is benchmarked into `dir`.
+fn write_fixture(dir: &Path, code_points: usize) -> anyhow::Result<()> {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir)?;
+
+    let config_ron = "\
+        Config(root: {\n\
+            \t\"Code points\": {\"extends\": [\"codepoints.ron\"]},\n\
+            \t\"Variations\": {\"extends\": [\"variations.ron\"]},\n\
+        })\n";
+    fs::write(dir.join("config.ron"), config_ron)?;
+
+    // Every scalar value from U+00A0 on, which are all allowed unescaped in RON strings
+    let chars = (0xA0..)
+        .filter_map(char::from_u32)
+        .take(code_points)
+        .collect::<Vec<_>>();
+
+    let mut codepoints_ron = String::from("{\n");
+    for &c in &chars {
+        let code_point = u32::from(c);
+        writeln!(
+            codepoints_ron,
+            "\t\"U+{code_point:04X}\\t{c}\\tCHARACTER {code_point:X} \
+            (<small>ALIAS {code_point:X} &amp; MORE</small>)\": \"{c}\","
+        )?;
+    }
+    codepoints_ron.push_str("}\n");
+    fs::write(dir.join("codepoints.ron"), codepoints_ron)?;
+
+    // A list of each thousandth character, with twenty variations of it in a submenu
+    let mut variations_ron = String::from("{\n");
+    for &c in chars.iter().step_by(1000) {
+        writeln!(
+            variations_ron,
+            "\t\"{c}\\t<b>base {:X}</b>\": {{",
+            u32::from(c)
+        )?;
+        for variation in 0..20 {
+            writeln!(
+                variations_ron,
+                "\t\t\"{c}{variation}\\t<i>variation {variation}</i>\": \"{c}{variation}\","
+            )?;
+        }
+        variations_ron.push_str("\t},\n");
+    }
+    variations_ron.push_str("}\n");
+    fs::write(dir.join("variations.ron"), variations_ron)?;
+
+    Ok(())
+}
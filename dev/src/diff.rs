@@ -0,0 +1,88 @@
+use {
+    crate::generate::{Content, Items},
+    ::{
+        anyhow::Context as _,
+        clap::Parser,
+        std::{
+            collections::BTreeMap,
+            fs,
+            path::{Path, PathBuf},
+        },
+    },
+};
+
+/// Compare two generated configuration files, such as `codepoints.ron` from two Unicode versions.
+/// Entries are matched by the glyph they copy.
+/// Fails if there are any differences.
+#[derive(Parser)]
+pub struct Args {
+    /// The old file.
+    old: PathBuf,
+
+    /// The new file.
+    new: PathBuf,
+}
+
+pub(crate) fn diff(Args { old, new }: Args) -> anyhow::Result<()> {
+    let old = glyphs(&*old)?;
+    let new = glyphs(&*new)?;
+
+    let mut added = 0;
+    let mut removed = 0;
+    let mut renamed = 0;
+
+    for (glyph, old_name) in &old {
+        match new.get(glyph) {
+            None => {
+                println!("- {glyph:?}: {old_name}");
+                removed += 1;
+            }
+            Some(new_name) if new_name != old_name => {
+                println!("~ {glyph:?}: {old_name} -> {new_name}");
+                renamed += 1;
+            }
+            Some(_) => {}
+        }
+    }
+    for (glyph, new_name) in &new {
+        if !old.contains_key(glyph) {
+            println!("+ {glyph:?}: {new_name}");
+            added += 1;
+        }
+    }
+
+    println!("{added} added, {removed} removed, {renamed} renamed");
+
+    anyhow::ensure!(
+        added + removed + renamed == 0,
+        "the files contain different entries"
+    );
+
+    Ok(())
+}
+
+/// Map every glyph in a generated file to the name of the first entry that copies it.
+fn glyphs(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let ron = fs::read_to_string(path)
+        .with_context(|| format!("failed to read file {}", path.display()))?;
+    let items =
+        Items::parse(&ron).with_context(|| format!("failed to parse file {}", path.display()))?;
+
+    let mut glyphs = BTreeMap::new();
+    visit(&items, &mut glyphs);
+    Ok(glyphs)
+}
+
+fn visit(items: &Items, glyphs: &mut BTreeMap<String, String>) {
+    for item in &items.direct {
+        match &item.content {
+            Content::Text(glyph) => {
+                glyphs
+                    .entry(glyph.clone())
+                    .or_insert_with(|| item.name.clone());
+            }
+            Content::Items(nested) => visit(nested, glyphs),
+            Content::Separator => {}
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Grouping of characters by their bidirectional behaviour.
+//!
+//! See <https://www.unicode.org/reports/tr9/>.
+
+use super::{
+    ucd::{self, CodePointItem},
+    Content, Deduplicated, Item, Items, Opts,
+};
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let groups: [(&str, fn(&str) -> bool); 2] = [
+        ("Bidirectional Controls", ucd::is_explicit_bidi),
+        ("Right-to-Left", ucd::is_strong_rtl),
+    ];
+
+    let items = groups
+        .into_iter()
+        .map(|(name, in_group)| {
+            let mut items = Deduplicated::new(opts.duplicates);
+            for codepoint in codepoints {
+                if in_group(codepoint.bidi_class) {
+                    items.push(codepoint.item.clone());
+                }
+            }
+            Item {
+                name: name.to_owned(),
+                search_aliases: Vec::new(),
+                content: Content::Items(items.into_items()),
+            }
+        })
+        .collect();
+
+    opts.write_ron("bidi.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
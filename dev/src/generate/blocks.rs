@@ -0,0 +1,105 @@
+//! Grouping of characters by the blocks listed in `Blocks.txt`.
+//!
+//! See <http://www.unicode.org/reports/tr44/#Blocks.txt>.
+
+use {
+    super::{
+        de_ucd, properties,
+        ucd::{BlocksLine, CodePointItem, BLOCKS},
+        Content, Deduplicated, Item, Items, Opts,
+    },
+    ::anyhow::Context as _,
+};
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let blocks_txt = opts.load_text_unicode(BLOCKS)?;
+    let blocks = de_ucd::lines::<BlocksLine<'_>>(&*blocks_txt)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to parse {BLOCKS}"))?;
+
+    let ranges = blocks.iter().map(|line| (line.name, &line.range));
+    let items = properties::group_by_ranges(ranges, codepoints)
+        .into_iter()
+        // Such as the blocks of surrogates and private use characters
+        .filter(|(_, indices)| !indices.is_empty())
+        .map(|(block, indices)| {
+            let mut items = Deduplicated::new(opts.duplicates);
+            for i in indices {
+                items.push(codepoints[i].item.clone());
+            }
+            Item {
+                name: block.to_owned(),
+                search_aliases: Vec::new(),
+                content: Content::Items(items.into_items()),
+            }
+        })
+        .collect();
+
+    opts.write_ron("blocks.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            super::{codepoint_item, temp_dir},
+            *,
+        },
+        std::fs,
+    };
+
+    #[test]
+    fn code_point_order() {
+        // In alphabetical order rather than order of code point
+        let blocks_txt = "\
+            0370..03FF; Greek and Coptic\n\
+            0000..007F; Basic Latin\n\
+            D800..DB7F; High Surrogates\n\
+            0080..00FF; Latin-1 Supplement\n";
+        let dir = temp_dir("blocks-code-point-order", &[(BLOCKS, blocks_txt)]);
+        let codepoints = [
+            codepoint_item(0x41, "LATIN CAPITAL LETTER A"),
+            codepoint_item(0x42, "LATIN CAPITAL LETTER B"),
+            codepoint_item(0xE9, "LATIN SMALL LETTER E WITH ACUTE"),
+            codepoint_item(0x3B1, "GREEK SMALL LETTER ALPHA"),
+        ];
+        generate(&Opts::for_tests(&*dir), &codepoints).unwrap();
+
+        let blocks_ron = fs::read_to_string(dir.join("blocks.ron")).unwrap();
+        let items = Items::parse(&blocks_ron).unwrap();
+        let blocks = items
+            .direct
+            .iter()
+            .map(|block| {
+                let items = match &block.content {
+                    Content::Items(items) => &items.direct,
+                    _ => panic!("{:?} is not a submenu", block.name),
+                };
+                let names = items.iter().map(|item| &*item.name).collect::<Vec<_>>();
+                (&*block.name, names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            [
+                (
+                    "Basic Latin",
+                    vec![
+                        "U+0041\tA\tLATIN CAPITAL LETTER A",
+                        "U+0042\tB\tLATIN CAPITAL LETTER B",
+                    ],
+                ),
+                (
+                    "Latin-1 Supplement",
+                    vec!["U+00E9\té\tLATIN SMALL LETTER E WITH ACUTE"],
+                ),
+                (
+                    "Greek and Coptic",
+                    vec!["U+03B1\tα\tGREEK SMALL LETTER ALPHA"],
+                ),
+            ],
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Grouping of characters by their major general category.
+//!
+//! See <http://www.unicode.org/reports/tr44/#General_Category_Values>.
+
+use super::{ucd::CodePointItem, Content, Deduplicated, Item, Items, Opts};
+
+/// Every major general category, identified by the first letter of its subcategories.
+const CATEGORIES: &[(char, &str)] = &[
+    ('L', "Letter"),
+    ('M', "Mark"),
+    ('N', "Number"),
+    ('P', "Punctuation"),
+    ('S', "Symbol"),
+    ('Z', "Separator"),
+    ('C', "Other"),
+];
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let mut items = Vec::new();
+
+    for &(letter, category_name) in CATEGORIES {
+        let mut category = Deduplicated::new(opts.duplicates);
+        for codepoint in codepoints {
+            if codepoint.general_category.starts_with(letter) {
+                category.push(codepoint.item.clone());
+            }
+        }
+
+        items.push(Item {
+            name: category_name.to_owned(),
+            search_aliases: Vec::new(),
+            content: Content::Items(category.into_items()),
+        });
+    }
+
+    opts.write_ron("categories.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Parsing of emoji keywords from CLDR annotation files, such as `common/annotations/en.xml`.
+//!
+//! See <https://cldr.unicode.org/translation/characters-emoji-symbols/short-names-and-keywords>.
+
+use ::{anyhow::Context as _, std::collections::HashMap};
+
+/// The keywords of every annotated emoji.
+pub(super) struct Annotations {
+    /// Map from each emoji, as returned by [`key`], to its keywords.
+    keywords: HashMap<String, Vec<String>>,
+}
+
+impl Annotations {
+    pub(super) fn parse(file: &str) -> anyhow::Result<Self> {
+        let mut keywords = HashMap::new();
+
+        for (i, line) in file.lines().enumerate() {
+            let annotation = parse_annotation_line(line)
+                .with_context(|| format!("error parsing annotation file line {}", i + 1))?;
+            if let Some((emoji, emoji_keywords)) = annotation {
+                keywords.insert(key(&*emoji), emoji_keywords);
+            }
+        }
+
+        Ok(Self { keywords })
+    }
+
+    /// Get the keywords of an emoji, if it has any.
+    pub(super) fn keywords(&self, emoji: &str) -> &[String] {
+        self.keywords
+            .get(&*key(emoji))
+            .map_or(&[], |keywords| &**keywords)
+    }
+}
+
+/// CLDR annotates emoji without their variation selectors.
+fn key(emoji: &str) -> String {
+    emoji.replace(['\u{FE0E}', '\u{FE0F}'], "")
+}
+
+/// Parse a line of the form `<annotation cp="😀">face | grin | grinning face</annotation>`.
+fn parse_annotation_line(line: &str) -> anyhow::Result<Option<(String, Vec<String>)>> {
+    let rest = match line.trim().strip_prefix("<annotation ") {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    let (attributes, rest) = rest
+        .split_once('>')
+        .context("unterminated annotation tag")?;
+
+    // Text-to-speech annotations contain the emoji's name, which we already have.
+    if attributes.contains("type=\"tts\"") {
+        return Ok(None);
+    }
+
+    let cp = attributes
+        .split_once("cp=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .map(|(cp, _)| cp)
+        .context("annotation has no cp attribute")?;
+    let value = rest
+        .strip_suffix("</annotation>")
+        .context("annotation does not end on the same line")?;
+
+    let keywords = value
+        .split('|')
+        .map(|keyword| unescape(keyword.trim()))
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Some((unescape(cp)?, keywords)))
+}
+
+/// Replace the XML entities and character references in a string.
+fn unescape(s: &str) -> anyhow::Result<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some((before, after)) = rest.split_once('&') {
+        unescaped.push_str(before);
+        let (entity, after) = after.split_once(';').context("unterminated entity")?;
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code_point = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16)
+                } else if let Some(decimal) = entity.strip_prefix('#') {
+                    decimal.parse()
+                } else {
+                    anyhow::bail!("unknown entity &{entity};");
+                }
+                .with_context(|| format!("invalid character reference &{entity};"))?;
+                char::from_u32(code_point)
+                    .with_context(|| format!("invalid character reference &{entity};"))?
+            }
+        };
+        unescaped.push(c);
+        rest = after;
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
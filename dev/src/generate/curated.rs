@@ -0,0 +1,81 @@
+//! Small hand-picked lists of commonly needed characters,
+//! kept separate from the exhaustive lists generated from the Unicode data files.
+
+use {
+    super::{
+        ucd::{CodePoint, CodePointItem},
+        Content, Item, Items, Opts,
+    },
+    ::anyhow::Context as _,
+};
+
+/// Every curated list, with the code points it contains in the order they are shown.
+const LISTS: &[(&str, &[&str])] = &[
+    (
+        "Currencies",
+        &[
+            "U+0024", "U+00A2", "U+00A3", "U+00A5", "U+20AC", "U+20B9", "U+20BD", "U+20A9",
+            "U+20BA", "U+20BF", "U+20A3", "U+20AA",
+        ],
+    ),
+    (
+        "Fractions",
+        &[
+            "U+00BD", "U+2153", "U+2154", "U+00BC", "U+00BE", "U+2155", "U+2156", "U+2157",
+            "U+2158", "U+2159", "U+215A", "U+2150", "U+215B", "U+215C", "U+215D", "U+215E",
+            "U+2151", "U+2152", "U+2044",
+        ],
+    ),
+    (
+        "Arrows (common)",
+        &[
+            "U+2190", "U+2191", "U+2192", "U+2193", "U+2194", "U+2195", "U+21D0", "U+21D2",
+            "U+21D4", "U+21A9", "U+21AA", "U+27F6", "U+21BA", "U+21BB",
+        ],
+    ),
+    (
+        "Quotes",
+        &[
+            "U+2018", "U+2019", "U+201C", "U+201D", "U+201A", "U+201E", "U+2039", "U+203A",
+            "U+00AB", "U+00BB", "U+300C", "U+300D",
+        ],
+    ),
+];
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let mut items = Vec::new();
+
+    for &(list_name, list) in LISTS {
+        let mut direct = Vec::new();
+        for written in list {
+            let code_point = parse_code_point(written)
+                .with_context(|| format!("invalid code point {written:?} in {list_name:?}"))?;
+            match codepoints.binary_search_by_key(&code_point, |item| item.code_point) {
+                Ok(i) => direct.push(codepoints[i].item.clone()),
+                // Characters the font can't display have already been left out.
+                Err(_) if opts.font.is_some() => {}
+                Err(_) => {
+                    anyhow::bail!("{written} in {list_name:?} has no entry in the Unicode data")
+                }
+            }
+        }
+
+        items.push(Item {
+            name: list_name.to_owned(),
+            search_aliases: Vec::new(),
+            content: Content::Items(Items::from_direct(direct)),
+        });
+    }
+
+    opts.write_ron("curated.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+fn parse_code_point(written: &str) -> anyhow::Result<CodePoint> {
+    let hex = written
+        .strip_prefix("U+")
+        .context("code point does not start with U+")?;
+    let code_point = u32::from_str_radix(hex, 16).context("code point is not hexadecimal")?;
+    Ok(CodePoint(code_point))
+}
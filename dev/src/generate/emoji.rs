@@ -1,77 +1,282 @@
 use {
-    super::{with_glib_markup_escaped, Content, Item, Items, Opts},
-    ::{anyhow::Context as _, std::collections::HashMap},
+    super::{
+        cldr, with_glib_markup_escaped, Content, DuplicatePolicy, Item, Items, Opts,
+        ALIAS_SEPARATOR,
+    },
+    ::{
+        anyhow::Context as _,
+        std::collections::{hash_map, HashMap, HashSet},
+    },
 };
 
+// Relative to `--emoji-base`
+pub(super) const EMOJI_LIST: &str = "emoji-list.txt";
+pub(super) const EMOJI_MODIFIERS: &str = "full-emoji-modifiers.txt";
+pub(super) const EMOJI_ORDERING: &str = "emoji-ordering.txt";
+
 pub(super) fn generate(opts: &Opts<'_>) -> anyhow::Result<()> {
-    const EMOJI_LIST: &str = "emoji/charts/emoji-list.txt";
-    let emojis_txt = opts.load_text_unicode(EMOJI_LIST)?;
+    let emojis_txt = opts.load_text_emoji(EMOJI_LIST)?;
     let emojis = parse_emoji_list(&*emojis_txt)
         .map(|res| res.with_context(|| format!("failed to parse {EMOJI_LIST}")));
 
-    const EMOJI_MODIFIERS: &str = "emoji/charts/full-emoji-modifiers.txt";
-    let modifiers_txt = opts.load_text_unicode(EMOJI_MODIFIERS)?;
+    let modifiers_txt = opts.load_text_emoji(EMOJI_MODIFIERS)?;
     let modifiers = parse_emoji_list(&*modifiers_txt)
         .map(|res| res.with_context(|| format!("failed to parse {EMOJI_MODIFIERS}")));
 
-    const EMOJI_ORDERING: &str = "emoji/charts/emoji-ordering.txt";
-    let ordering_txt = opts.load_text_unicode(EMOJI_ORDERING)?;
+    let ordering_txt = opts.load_text_emoji(EMOJI_ORDERING)?;
     let ordering = parse_emoji_order(&*ordering_txt)
         .map(|res| res.with_context(|| format!("failed to parse {EMOJI_ORDERING}")));
 
-    let mut all_emojis = Iterator::chain(
+    let annotations = opts
+        .cldr_annotations
+        .as_deref()
+        .map(|place| {
+            let annotations_txt = opts.load_text(place)?;
+            cldr::Annotations::parse(&*annotations_txt)
+                .with_context(|| format!("failed to parse {place}"))
+        })
+        .transpose()?;
+
+    // Only ever used for lookups: the order of the output comes solely from the ordering file,
+    // so that generating from the same files always produces the same bytes.
+    let mut all_emojis = HashMap::<String, (String, bool)>::new();
+    for res in Iterator::chain(
         emojis.map(|res| res.map(|(value, description)| (value, (description, false)))),
         modifiers.map(|res| res.map(|(value, description)| (value, (description, true)))),
-    )
-    .collect::<anyhow::Result<HashMap<_, _>>>()?;
+    ) {
+        let (value, (description, variation)) = res?;
+        match all_emojis.entry(value) {
+            hash_map::Entry::Occupied(mut entry) => {
+                eprintln!(
+                    "warning: {:?} and {description:?} are both the emoji {}",
+                    entry.get().0,
+                    entry.key(),
+                );
+                if let DuplicatePolicy::Merge = opts.duplicates {
+                    let existing = &mut entry.get_mut().0;
+                    existing.push_str(" | ");
+                    existing.push_str(description);
+                }
+            }
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert((description.to_owned(), variation));
+            }
+        }
+    }
 
-    let ordered = ordering
-        .map(|res| {
-            let emoji = res?;
-            let (description, variation) = all_emojis
-                .remove(&*emoji)
-                .with_context(|| format!("emoji {} found in ordering but not in list", emoji))?;
+    let mut seen = HashSet::new();
+    let mut ordered_emojis = Vec::new();
+    for res in ordering {
+        let emoji = res?;
+        if !seen.insert(emoji.clone()) {
+            eprintln!("warning: emoji {emoji} appears more than once in the ordering");
+            continue;
+        }
+        ordered_emojis.push(emoji);
+    }
 
-            let name = with_glib_markup_escaped(description, |s| s.to_owned());
+    // The number of emojis that differ only in their variation selectors.
+    let mut presentations = HashMap::<String, usize>::new();
+    for emoji in &ordered_emojis {
+        *presentations
+            .entry(without_variation_selectors(emoji))
+            .or_default() += 1;
+    }
 
-            let item = Item {
-                name: format!("{emoji}\t{name}"),
-                content: Content::Text(emoji),
-            };
+    let mut ordered = Vec::new();
+    // The description of the last emoji that isn't a variation.
+    let mut base_description = String::new();
+    for emoji in ordered_emojis {
+        let (description, variation) = all_emojis
+            .remove(&*emoji)
+            .with_context(|| format!("emoji {} found in ordering but not in list", emoji))?;
 
-            Ok((item, variation))
-        })
-        .collect::<anyhow::Result<Vec<(Item, bool)>>>()?;
+        let key = without_variation_selectors(&*emoji);
+        let tones = emoji
+            .chars()
+            .filter(|&c| skin_tone_name(c).is_some())
+            .collect::<Vec<_>>();
+        // Emojis of two people with a skin tone each are nested in a submenu for the first tone,
+        // and labelled by the second.
+        // Those of more people are left flat, as the combinations would be too many to browse.
+        let mut first_tone = None;
+        // Variations are shown in a submenu named after their base emoji,
+        // so they are labelled by only what distinguishes them from it.
+        let grouped = variation && !opts.emoji_flat;
+        let mut label = if let (true, &[first, second]) = (grouped, &*tones) {
+            let submenu_name = with_glib_markup_escaped(skin_tone_name(first).unwrap(), |s| {
+                format!("{emoji}\t{s}")
+            });
+            first_tone = Some((first, submenu_name));
+            skin_tone_name(second).unwrap().to_owned()
+        } else if grouped {
+            distinguishing_suffix(&*base_description, &*description).to_owned()
+        } else {
+            base_description = description.clone();
+            description.clone()
+        };
+        if presentations[&*key] > 1 {
+            label.push_str(if emoji.contains('\u{FE0F}') {
+                " (emoji presentation)"
+            } else {
+                " (text presentation)"
+            });
+        }
 
-    anyhow::ensure!(all_emojis.is_empty(), "emojis in list but not ordering");
+        let mut name = with_glib_markup_escaped(&*label, |s| format!("{emoji}\t{s}"));
 
-    let mut items = Vec::new();
+        // Emojis without annotations are left without keywords.
+        if let Some(annotations) = &annotations {
+            let keywords = annotations
+                .keywords(&*emoji)
+                .iter()
+                .filter(|keyword| **keyword != description)
+                .map(|keyword| &**keyword)
+                .collect::<Vec<_>>();
+            if !keywords.is_empty() {
+                opts.push_aliases(&mut name, &*keywords.join(ALIAS_SEPARATOR));
+            }
+        }
 
-    let mut ordered = ordered.into_iter().peekable();
-    while let Some((base, is_variation)) = ordered.next() {
-        assert!(!is_variation);
+        let mut search_aliases = Vec::new();
+        if opts.emoji_codepoints {
+            let codepoints = emoji
+                .chars()
+                .map(|c| format!("U+{:04X}", u32::from(c)))
+                .collect::<Vec<_>>();
+            search_aliases.push(codepoints.join(" "));
+        }
 
-        // If there are 1+ variations of this emoji, put them in a list.
-        let item = if ordered.peek().map_or(false, |(_, variation)| *variation) {
-            let mut variations = vec![base];
-            while let Some((item, _)) = ordered.next_if(|(_, variation)| *variation) {
-                variations.push(item);
-            }
-            Item {
-                name: variations[0].name.clone(),
-                content: Content::Items(Items::from_direct(variations)),
-            }
-        } else {
-            base
+        let item = Item {
+            name,
+            search_aliases,
+            content: Content::Text(emoji),
         };
-        items.push(item);
+
+        ordered.push((key, item, variation, first_tone));
     }
 
-    opts.write_ron("emojis.ron", Items::from_direct(items))?;
+    if !all_emojis.is_empty() {
+        // Sorted so that the error is the same on every run.
+        let mut unordered = all_emojis.into_keys().collect::<Vec<_>>();
+        unordered.sort_unstable();
+        anyhow::bail!("emojis in list but not ordering: {}", unordered.join(" "));
+    }
+
+    if opts.emoji_flat {
+        let items = ordered.into_iter().map(|(_, item, ..)| item).collect();
+        return opts.write_ron("emojis.ron", Items::from_direct(items));
+    }
+
+    // Groups of emojis that are shown in a single submenu if there is more than one of them.
+    let mut groups = Vec::<Vec<Item>>::new();
+    let mut group_indices = HashMap::<String, usize>::new();
+    let mut last_group = None;
+    // Map from a group and the first skin tone of a two-person emoji
+    // to the index of its submenu in the group.
+    let mut tone_submenus = HashMap::<(usize, char), usize>::new();
+
+    for (key, item, is_variation, first_tone) in ordered {
+        // Variations of an emoji always directly follow it.
+        if is_variation {
+            let group = last_group.with_context(|| {
+                format!("variation {key} comes before any emoji it could be a variation of")
+            })?;
+            let (tone, submenu_name) = match first_tone {
+                Some(first_tone) => first_tone,
+                None => {
+                    groups[group].push(item);
+                    continue;
+                }
+            };
+            let index = *tone_submenus.entry((group, tone)).or_insert_with(|| {
+                groups[group].push(Item {
+                    name: submenu_name,
+                    search_aliases: Vec::new(),
+                    content: Content::Items(Items::from_direct(Vec::new())),
+                });
+                groups[group].len() - 1
+            });
+            match &mut groups[group][index].content {
+                Content::Items(items) => items.direct.push(item),
+                _ => unreachable!(),
+            }
+            continue;
+        }
+        let group = *group_indices.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(item);
+        last_group = Some(group);
+    }
+
+    let items = groups
+        .into_iter()
+        .map(|group| Item {
+            name: group[0].name.clone(),
+            search_aliases: Vec::new(),
+            content: Content::Items(Items::from_direct(group)),
+        })
+        .collect();
+
+    let mut items = Items::from_direct(items);
+    // Groups of a single emoji are just that emoji.
+    items.flatten_single_item_submenus();
+
+    opts.write_ron("emojis.ron", items)?;
 
     Ok(())
 }
 
+/// Strip the words a variation's description shares with the description of its base emoji,
+/// such as `person running: light skin tone` to `light skin tone`.
+///
+/// If nothing is left, the whole description is returned.
+fn distinguishing_suffix<'a>(base: &str, description: &'a str) -> &'a str {
+    let is_word_boundary = |i: usize| {
+        let before = description[..i].chars().next_back();
+        let after = description[i..].chars().next();
+        !matches!((before, after), (Some(a), Some(b)) if a.is_alphanumeric() && b.is_alphanumeric())
+    };
+
+    let mut common = base
+        .bytes()
+        .zip(description.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !description.is_char_boundary(common) || !is_word_boundary(common) {
+        common -= 1;
+    }
+
+    match description[common..].trim_start_matches(|c: char| !c.is_alphanumeric()) {
+        "" => description,
+        suffix => suffix,
+    }
+}
+
+/// The name of a skin tone modifier as used in emoji descriptions,
+/// or `None` if the character is not one.
+fn skin_tone_name(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{1F3FB}' => "light skin tone",
+        '\u{1F3FC}' => "medium-light skin tone",
+        '\u{1F3FD}' => "medium skin tone",
+        '\u{1F3FE}' => "medium-dark skin tone",
+        '\u{1F3FF}' => "dark skin tone",
+        _ => return None,
+    })
+}
+
+/// Strip the variation selectors U+FE0E and U+FE0F from an emoji,
+/// so that its text and emoji presentations compare equal.
+fn without_variation_selectors(emoji: &str) -> String {
+    emoji
+        .chars()
+        .filter(|&c| c != '\u{FE0E}' && c != '\u{FE0F}')
+        .collect()
+}
+
 fn parse_emoji_list(file: &str) -> impl '_ + Iterator<Item = anyhow::Result<(String, &str)>> {
     file.lines()
         .enumerate()
@@ -130,3 +335,275 @@ fn parse_scalar_value(codepoint: &str) -> anyhow::Result<char> {
         char::from_u32(codepoint).with_context(|| format!("{codepoint:X} is a surrogate"))?;
     Ok(scalar_value)
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::temp_dir, *},
+        std::fs,
+    };
+
+    /// Generate `emojis.ron` from the contents of the emoji data files, returning the RON.
+    fn emojis_ron(
+        test_name: &str,
+        flat: bool,
+        list: &str,
+        modifiers: &str,
+        ordering: &str,
+    ) -> String {
+        let files = [
+            (EMOJI_LIST, list),
+            (EMOJI_MODIFIERS, modifiers),
+            (EMOJI_ORDERING, ordering),
+        ];
+        let dir = temp_dir(test_name, &files);
+        let mut opts = Opts::for_tests(&*dir);
+        opts.emoji_flat = flat;
+        generate(&opts).unwrap();
+        fs::read_to_string(dir.join("emojis.ron")).unwrap()
+    }
+
+    /// Generate `emojis.ron`, returning its items.
+    fn emojis(test_name: &str, flat: bool, list: &str, modifiers: &str, ordering: &str) -> Items {
+        Items::parse(&emojis_ron(test_name, flat, list, modifiers, ordering)).unwrap()
+    }
+
+    fn submenu(item: &Item) -> &Items {
+        match &item.content {
+            Content::Items(items) => items,
+            _ => panic!("{:?} is not a submenu", item.name),
+        }
+    }
+
+    fn text(item: &Item) -> &str {
+        match &item.content {
+            Content::Text(text) => text,
+            _ => panic!("{:?} is not a text item", item.name),
+        }
+    }
+
+    #[test]
+    fn presentations() {
+        let items = emojis(
+            "presentations",
+            false,
+            "2764\tred heart\n2764 FE0F\tred heart\n",
+            "",
+            "U+2764 U+FE0F ; 0.6 # ❤️ red heart\nU+2764 ; 0.6 # ❤ red heart\n",
+        );
+        assert_eq!(items.direct.len(), 1);
+
+        let presentations = submenu(&items.direct[0]);
+        let names = presentations
+            .direct
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "❤\u{FE0F}\tred heart (emoji presentation)",
+                "❤\tred heart (text presentation)",
+            ],
+        );
+        // The variation selector is only ignored for comparison.
+        assert_eq!(text(&presentations.direct[0]), "❤\u{FE0F}");
+        assert_eq!(text(&presentations.direct[1]), "❤");
+    }
+
+    #[test]
+    fn deterministic() {
+        let list = "\
+            1F600\tgrinning face\n\
+            1F44B\twaving hand\n\
+            2764 FE0F\tred heart\n\
+            1F3C3\tperson running\n";
+        let modifiers = "\
+            1F44B 1F3FB\twaving hand: light skin tone\n\
+            1F44B 1F3FF\twaving hand: dark skin tone\n\
+            1F3C3 1F3FD\tperson running: medium skin tone\n";
+        let ordering = "\
+            U+1F600 ; 1.0 # 😀 grinning face\n\
+            U+2764 U+FE0F ; 0.6 # ❤️ red heart\n\
+            U+1F44B ; 0.6 # 👋 waving hand\n\
+            U+1F44B U+1F3FB ; 1.0 # 👋🏻 waving hand: light skin tone\n\
+            U+1F44B U+1F3FF ; 1.0 # 👋🏿 waving hand: dark skin tone\n\
+            U+1F3C3 ; 0.6 # 🏃 person running\n\
+            U+1F3C3 U+1F3FD ; 1.0 # 🏃🏽 person running: medium skin tone\n";
+
+        let first = emojis_ron("deterministic-1", false, list, modifiers, ordering);
+        let second = emojis_ron("deterministic-2", false, list, modifiers, ordering);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn variation_labels() {
+        let items = emojis(
+            "variation-labels",
+            false,
+            "1F3C3\tperson running\n",
+            "\
+            1F3C3 1F3FB\tperson running: light skin tone\n\
+            1F3C3 1F3FF\tperson running: dark skin tone\n",
+            "\
+            U+1F3C3 ; 0.6 # 🏃 person running\n\
+            U+1F3C3 U+1F3FB ; 1.0 # 🏃🏻 person running: light skin tone\n\
+            U+1F3C3 U+1F3FF ; 1.0 # 🏃🏿 person running: dark skin tone\n",
+        );
+        assert_eq!(items.direct.len(), 1);
+        let variations = submenu(&items.direct[0]);
+        let names = variations
+            .direct
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "🏃\tperson running",
+                "🏃\u{1F3FB}\tlight skin tone",
+                "🏃\u{1F3FF}\tdark skin tone",
+            ],
+        );
+
+        assert_eq!(
+            distinguishing_suffix("person running", "person running facing right"),
+            "facing right"
+        );
+        assert_eq!(
+            distinguishing_suffix("person running", "person running"),
+            "person running"
+        );
+    }
+
+    #[test]
+    fn two_person_skin_tones() {
+        let people = "1F9D1 200D 1F91D 200D 1F9D1";
+        let toned =
+            |first: &str, second: &str| format!("1F9D1 {first} 200D 1F91D 200D 1F9D1 {second}");
+        let (light, dark) = ("1F3FB", "1F3FF");
+        let combinations = [(light, light), (light, dark), (dark, light), (dark, dark)];
+
+        let list = format!("{people}\tpeople holding hands\n");
+        let mut modifiers = String::new();
+        let mut ordering = format!(
+            "U+{} ; 12.0 # people holding hands\n",
+            people.replace(' ', " U+")
+        );
+        for (first, second) in combinations {
+            let tone_name = |tone| if tone == light { "light" } else { "dark" };
+            let description = format!(
+                "people holding hands: {} skin tone, {} skin tone",
+                tone_name(first),
+                tone_name(second),
+            );
+            let toned = toned(first, second);
+            modifiers.push_str(&*format!("{toned}\t{description}\n"));
+            ordering.push_str(&*format!(
+                "U+{} ; 12.0 # {description}\n",
+                toned.replace(' ', " U+")
+            ));
+        }
+
+        let items = emojis(
+            "two-person-skin-tones",
+            false,
+            &*list,
+            &*modifiers,
+            &*ordering,
+        );
+        assert_eq!(items.direct.len(), 1);
+        let group = submenu(&items.direct[0]);
+        let names = |items: &Items| {
+            items
+                .direct
+                .iter()
+                .map(|item| item.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let emoji = |codepoints: &str| {
+            codepoints
+                .split(' ')
+                .map(|c| char::from_u32(u32::from_str_radix(c, 16).unwrap()).unwrap())
+                .collect::<String>()
+        };
+
+        // Nested by the first person's skin tone, and labelled by the second's
+        assert_eq!(
+            names(group),
+            [
+                format!("{}\tpeople holding hands", emoji(people)),
+                format!("{}\tlight skin tone", emoji(&*toned(light, light))),
+                format!("{}\tdark skin tone", emoji(&*toned(dark, light))),
+            ],
+        );
+        assert_eq!(
+            names(submenu(&group.direct[1])),
+            [
+                format!("{}\tlight skin tone", emoji(&*toned(light, light))),
+                format!("{}\tdark skin tone", emoji(&*toned(light, dark))),
+            ],
+        );
+        assert_eq!(
+            text(&submenu(&group.direct[2]).direct[0]),
+            emoji(&*toned(dark, light))
+        );
+    }
+
+    #[test]
+    fn variation_first() {
+        let files = [
+            (EMOJI_LIST, ""),
+            (
+                EMOJI_MODIFIERS,
+                "1F3C3 1F3FB\tperson running: light skin tone\n",
+            ),
+            (
+                EMOJI_ORDERING,
+                "U+1F3C3 U+1F3FB ; 1.0 # 🏃🏻 person running: light skin tone\n",
+            ),
+        ];
+        let dir = temp_dir("variation-first", &files);
+        let e = generate(&Opts::for_tests(&*dir)).unwrap_err();
+        assert!(e.to_string().contains("comes before any emoji"), "{e:?}");
+    }
+
+    #[test]
+    fn flat() {
+        let items = emojis(
+            "flat",
+            true,
+            "1F3C3\tperson running\n2764\tred heart\n2764 FE0F\tred heart\n",
+            "\
+            1F3C3 1F3FB\tperson running: light skin tone\n\
+            1F3C3 1F3FF\tperson running: dark skin tone\n",
+            "\
+            U+1F3C3 ; 0.6 # 🏃 person running\n\
+            U+1F3C3 U+1F3FB ; 1.0 # 🏃🏻 person running: light skin tone\n\
+            U+1F3C3 U+1F3FF ; 1.0 # 🏃🏿 person running: dark skin tone\n\
+            U+2764 U+FE0F ; 0.6 # ❤️ red heart\n\
+            U+2764 ; 0.6 # ❤ red heart\n",
+        );
+        // No submenus at all
+        assert!(items
+            .direct
+            .iter()
+            .all(|item| matches!(item.content, Content::Text(_))));
+        let names = items
+            .direct
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        // Variations are labelled by their whole description, as there is no submenu to name.
+        assert_eq!(
+            names,
+            [
+                "🏃\tperson running",
+                "🏃\u{1F3FB}\tperson running: light skin tone",
+                "🏃\u{1F3FF}\tperson running: dark skin tone",
+                "❤\u{FE0F}\tred heart (emoji presentation)",
+                "❤\tred heart (text presentation)",
+            ],
+        );
+    }
+}
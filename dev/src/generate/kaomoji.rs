@@ -0,0 +1,57 @@
+//! Generation of kaomoji from the curated list in `kaomoji.txt`.
+
+use {
+    super::{with_glib_markup_escaped, Content, Item, Items, Opts},
+    ::anyhow::Context as _,
+};
+
+const KAOMOJI_TXT: &str = include_str!("kaomoji.txt");
+
+pub(super) fn generate(opts: &Opts<'_>) -> anyhow::Result<()> {
+    let mut categories = Vec::<(&str, Vec<Item>)>::new();
+
+    for (i, line) in KAOMOJI_TXT.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (category, name, kaomoji) = parse_kaomoji_line(line)
+            .with_context(|| format!("error parsing kaomoji.txt line {}", i + 1))?;
+
+        let name = with_glib_markup_escaped(&*format!("{kaomoji}\t{name}"), |s| s.to_owned());
+        let item = Item {
+            name,
+            search_aliases: Vec::new(),
+            content: Content::Text(kaomoji.to_owned()),
+        };
+
+        match categories
+            .iter_mut()
+            .find(|(existing, _)| *existing == category)
+        {
+            Some((_, items)) => items.push(item),
+            None => categories.push((category, vec![item])),
+        }
+    }
+
+    let items = categories
+        .into_iter()
+        .map(|(category, items)| Item {
+            name: with_glib_markup_escaped(category, |s| s.to_owned()),
+            search_aliases: Vec::new(),
+            content: Content::Items(Items::from_direct(items)),
+        })
+        .collect();
+
+    opts.write_ron("kaomoji.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+fn parse_kaomoji_line(line: &str) -> anyhow::Result<(&str, &str, &str)> {
+    let mut fields = line.splitn(3, '\t');
+    let category = fields.next().unwrap();
+    let name = fields.next().context("line does not contain a name")?;
+    let kaomoji = fields.next().context("line does not contain a kaomoji")?;
+    anyhow::ensure!(!kaomoji.is_empty(), "found empty kaomoji");
+    Ok((category, name, kaomoji))
+}
@@ -1,12 +1,29 @@
 use ::{
     anyhow::Context as _,
     clap::Parser,
+    rofi_unicode::config,
     serde::ser::{Serialize, SerializeMap, Serializer},
-    std::{ffi::CStr, fs, path::Path, str},
+    std::{
+        cell::RefCell,
+        collections::{hash_map, HashMap},
+        ffi::CStr,
+        fs,
+        path::Path,
+        str,
+    },
 };
 
+mod bidi;
+mod blocks;
+mod categories;
+mod cldr;
+mod curated;
 mod de_ucd;
 mod emoji;
+mod kaomoji;
+mod nerdfonts;
+mod properties;
+mod scripts;
 mod ucd;
 
 /// Generate all the system-wide default configuration files and put them in `./etc`.
@@ -15,50 +32,439 @@ pub struct Args {
     /// URL or filesystem path to the Unicode website.
     #[clap(long, default_value = "https://www.unicode.org/", env)]
     unicode: String,
+
+    /// What to do when two generated entries copy the same text.
+    #[clap(long, arg_enum, default_value = "skip")]
+    duplicates: DuplicatePolicy,
+
+    /// Path to the directory of emoji data files, relative to `--unicode`,
+    /// such as `Public/emoji/latest/` or a version-specific directory.
+    #[clap(long, default_value = "emoji/charts/")]
+    emoji_base: String,
+
+    /// Also generate `categories.ron`, grouping characters by their general category.
+    #[clap(long)]
+    categories: bool,
+
+    /// Also generate `bidi.ron`, grouping the characters that control the direction of text
+    /// and those written right-to-left.
+    #[clap(long)]
+    bidi: bool,
+
+    /// Also generate `blocks.ron`, grouping characters by their block, according to `Blocks.txt`.
+    #[clap(long)]
+    blocks: bool,
+
+    /// Also generate `scripts.ron`, grouping characters by their script,
+    /// according to `Scripts.txt`.
+    #[clap(long)]
+    scripts: bool,
+
+    /// URL or filesystem path to a CLDR annotations file such as `common/annotations/en.xml`,
+    /// whose keywords will be added to the aliases of each emoji.
+    #[clap(long)]
+    cldr_annotations: Option<String>,
+
+    /// URL or filesystem path to Nerd Fonts' `glyphnames.json`,
+    /// from which `nerdfonts.ron` will be generated.
+    #[clap(long)]
+    nerd_fonts: Option<String>,
+
+    /// Pango markup to wrap the aliases of each entry in, with `{}` in place of the aliases.
+    /// Pass an empty string to not wrap them in anything.
+    #[clap(long, default_value = "<small>{}</small>")]
+    alias_markup: String,
+
+    /// Don't put any markup in names, overriding `--alias-markup`,
+    /// so that they look the same under every Rofi theme.
+    /// Names are still escaped, so they must still be parsed as markup.
+    #[clap(long)]
+    no_markup: bool,
+
+    /// Pad each narrow glyph with a space so that it takes up as many columns as a wide glyph,
+    /// according to `EastAsianWidth.txt`.
+    /// This only lines up the columns of names when Rofi's theme uses a monospace font.
+    #[clap(long)]
+    east_asian_width: bool,
+
+    /// Put a separator before each block of characters in `codepoints.ron`,
+    /// according to `Blocks.txt`.
+    #[clap(long)]
+    block_separators: bool,
+
+    /// Generate the Hangul syllables, whose names are derived from `Jamo.txt`.
+    /// There are over eleven thousand of them, so they are left out otherwise.
+    #[clap(long)]
+    hangul_syllables: bool,
+
+    /// Make emojis searchable by the `U+XXXX` forms of their code points,
+    /// without displaying them.
+    #[clap(long)]
+    emoji_codepoints: bool,
+
+    /// Put every emoji, including each variation, directly in `emojis.ron`
+    /// instead of grouping variations into a submenu of their base emoji.
+    #[clap(long)]
+    emoji_flat: bool,
+
+    /// Leave out characters whose use is strongly discouraged,
+    /// according to the `Deprecated` property of `PropList.txt`.
+    /// Otherwise they are kept, with `deprecated` among their aliases.
+    #[clap(long)]
+    no_deprecated: bool,
+
+    /// Path to a font file; characters the font has no glyph for are left out,
+    /// so that nothing is generated that would only display as tofu.
+    /// Characters that are never displayed by themselves, such as controls, are always kept.
+    #[clap(long)]
+    font: Option<String>,
+
+    /// Never download anything,
+    /// failing if any required file is missing from the paths given instead.
+    #[clap(long)]
+    offline: bool,
+
+    /// Only run the given generator, ignoring `--categories`, `--bidi`, `--blocks` and `--scripts`;
+    /// can be passed more than once.
+    /// `default.ron` is not written, as it would leave out the files of every other generator.
+    #[clap(long, arg_enum)]
+    only: Vec<Generator>,
+}
+
+/// One of the generators of a group of files, which can be run without the others.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+enum Generator {
+    /// `codepoints.ron`.
+    Codepoints,
+    /// `categories.ron`.
+    Categories,
+    /// `bidi.ron`.
+    Bidi,
+    /// `blocks.ron`.
+    Blocks,
+    /// `scripts.ron`.
+    Scripts,
+    /// `properties.ron`.
+    Properties,
+    /// `curated.ron`.
+    Curated,
+    /// `emojis.ron`.
+    Emoji,
+    /// `kaomoji.ron`.
+    Kaomoji,
+    /// `nerdfonts.ron`, which requires `--nerd-fonts`.
+    NerdFonts,
+}
+
+impl Generator {
+    /// Every generator, in the order they are run.
+    const ALL: [Self; 10] = [
+        Self::Codepoints,
+        Self::Categories,
+        Self::Bidi,
+        Self::Blocks,
+        Self::Scripts,
+        Self::Properties,
+        Self::Curated,
+        Self::Emoji,
+        Self::Kaomoji,
+        Self::NerdFonts,
+    ];
+
+    /// Whether the generator needs the code points parsed from the UCD.
+    fn needs_codepoints(self) -> bool {
+        matches!(
+            self,
+            Self::Codepoints
+                | Self::Categories
+                | Self::Bidi
+                | Self::Blocks
+                | Self::Scripts
+                | Self::Properties
+                | Self::Curated
+        )
+    }
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum DuplicatePolicy {
+    /// Keep only the first entry.
+    Skip,
+    /// Keep the first entry, appending the name of the second to it.
+    Merge,
 }
 
-pub(crate) fn generate(Args { mut unicode }: Args) -> anyhow::Result<()> {
+pub(crate) fn generate(
+    Args {
+        mut unicode,
+        mut emoji_base,
+        duplicates,
+        categories,
+        bidi,
+        blocks,
+        scripts,
+        cldr_annotations,
+        nerd_fonts,
+        alias_markup,
+        no_markup,
+        east_asian_width,
+        block_separators,
+        hangul_syllables,
+        emoji_codepoints,
+        emoji_flat,
+        no_deprecated,
+        font,
+        offline,
+        only,
+    }: Args,
+) -> anyhow::Result<()> {
     if !unicode.ends_with('/') {
         unicode.push('/');
     }
+    if !emoji_base.ends_with('/') {
+        emoji_base.push('/');
+    }
+
+    let alias_markup = if no_markup || alias_markup.is_empty() {
+        (String::new(), String::new())
+    } else {
+        parse_markup_template(&*alias_markup)
+            .with_context(|| format!("invalid alias markup {alias_markup:?}"))?
+    };
+
+    let font = match font {
+        Some(path) => {
+            let data = fs::read(&*path).with_context(|| format!("failed to read font {path}"))?;
+            let font = Font { path, data };
+            // Check that the font can be parsed before anything is downloaded.
+            font.face()?;
+            Some(font)
+        }
+        None => None,
+    };
 
     let opts = Opts {
         agent: ureq::agent(),
         unicode,
+        emoji_base,
         out_dir: "etc".as_ref(),
+        duplicates,
+        cldr_annotations,
+        alias_markup,
+        east_asian_width,
+        block_separators,
+        hangul_syllables,
+        emoji_codepoints,
+        emoji_flat,
+        no_deprecated,
+        font,
+        offline,
+        written: RefCell::new(Vec::new()),
     };
 
-    ucd::generate(&opts)?;
-    emoji::generate(&opts)?;
+    let generators = Generator::ALL
+        .into_iter()
+        .filter(|&generator| {
+            if !only.is_empty() {
+                return only.contains(&generator);
+            }
+            match generator {
+                Generator::Categories => categories,
+                Generator::Bidi => bidi,
+                Generator::Blocks => blocks,
+                Generator::Scripts => scripts,
+                Generator::NerdFonts => nerd_fonts.is_some(),
+                _ => true,
+            }
+        })
+        .collect::<Vec<_>>();
+    if generators.contains(&Generator::NerdFonts) && nerd_fonts.is_none() {
+        anyhow::bail!("`--only nerd-fonts` requires `--nerd-fonts`");
+    }
+
+    if opts.offline {
+        check_offline(&opts, &*generators, nerd_fonts.as_deref())?;
+    }
+
+    let needs_codepoints = generators
+        .iter()
+        .any(|generator| generator.needs_codepoints());
+    let ucd_files = needs_codepoints
+        .then(|| ucd::Files::load(&opts))
+        .transpose()?;
+    let ucd_data = ucd_files.as_ref().map(ucd::Files::parse).transpose()?;
+    let codepoints = ucd_data
+        .as_ref()
+        .map(|ucd_data| ucd::codepoint_items(&opts, ucd_data))
+        .transpose()?
+        .unwrap_or_default();
+
+    for generator in generators {
+        match generator {
+            Generator::Codepoints => ucd::generate(&opts, &codepoints)?,
+            Generator::Categories => categories::generate(&opts, &codepoints)?,
+            Generator::Bidi => bidi::generate(&opts, &codepoints)?,
+            Generator::Blocks => blocks::generate(&opts, &codepoints)?,
+            Generator::Scripts => scripts::generate(&opts, &codepoints)?,
+            Generator::Properties => {
+                // The UCD is always loaded for this generator.
+                properties::generate(&opts, ucd_data.as_ref().unwrap(), &codepoints)?;
+            }
+            Generator::Curated => curated::generate(&opts, &codepoints)?,
+            Generator::Emoji => emoji::generate(&opts)?,
+            Generator::Kaomoji => kaomoji::generate(&opts)?,
+            Generator::NerdFonts => nerdfonts::generate(&opts, nerd_fonts.as_deref().unwrap())?,
+        }
+    }
+
+    if only.is_empty() {
+        write_default_config(&opts)?;
+    }
+
+    Ok(())
+}
+
+/// Check that every file needed to generate is available without downloading anything,
+/// listing all the files that aren't.
+fn check_offline(
+    opts: &Opts<'_>,
+    generators: &[Generator],
+    nerd_fonts: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut required = Vec::new();
+    if generators
+        .iter()
+        .any(|generator| generator.needs_codepoints())
+    {
+        required.extend([ucd::UNICODE_DATA, ucd::NAME_ALIASES, properties::PROP_LIST]);
+        if opts.east_asian_width {
+            required.push(ucd::EAST_ASIAN_WIDTH);
+        }
+        if opts.hangul_syllables {
+            required.push(ucd::JAMO);
+        }
+    }
+    if (generators.contains(&Generator::Codepoints) && opts.block_separators)
+        || generators.contains(&Generator::Blocks)
+    {
+        required.push(ucd::BLOCKS);
+    }
+    if generators.contains(&Generator::Scripts) {
+        required.push(scripts::SCRIPTS);
+    }
+    let mut required = required
+        .into_iter()
+        .map(|path| format!("{}{path}", opts.unicode))
+        .collect::<Vec<_>>();
+    if generators.contains(&Generator::Emoji) {
+        required.extend(
+            [
+                emoji::EMOJI_LIST,
+                emoji::EMOJI_MODIFIERS,
+                emoji::EMOJI_ORDERING,
+            ]
+            .into_iter()
+            .map(|file| format!("{}{}{file}", opts.unicode, opts.emoji_base)),
+        );
+        required.extend(opts.cldr_annotations.clone());
+    }
+    if generators.contains(&Generator::NerdFonts) {
+        required.extend(nerd_fonts.map(str::to_owned));
+    }
+
+    let missing = required
+        .into_iter()
+        .filter(|place| is_url(place) || !Path::new(place).is_file())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!("required files not found offline:\n{}", missing.join("\n"));
+    }
 
     Ok(())
 }
 
+/// Write `default.ron`, a ready-to-use configuration including every file generated in this run.
+fn write_default_config(opts: &Opts<'_>) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    #[serde(rename = "Config")]
+    struct DefaultConfig {
+        root: Items,
+    }
+
+    let extends = opts.written.take();
+    opts.write_ron(
+        "default.ron",
+        DefaultConfig {
+            root: Items {
+                extends,
+                direct: Vec::new(),
+            },
+        },
+    )
+}
+
 struct Opts<'a> {
     agent: ureq::Agent,
     unicode: String,
+    /// The directory of emoji data files relative to `unicode`, ending in a slash.
+    emoji_base: String,
     out_dir: &'a Path,
+    duplicates: DuplicatePolicy,
+    cldr_annotations: Option<String>,
+    /// The markup before and after the aliases of each entry.
+    alias_markup: (String, String),
+    east_asian_width: bool,
+    block_separators: bool,
+    hangul_syllables: bool,
+    emoji_codepoints: bool,
+    emoji_flat: bool,
+    no_deprecated: bool,
+    font: Option<Font>,
+    offline: bool,
+    /// The path of every file written so far, relative to `out_dir`.
+    written: RefCell<Vec<String>>,
 }
 
 impl Opts<'_> {
     fn load_text_unicode(&self, path: &str) -> anyhow::Result<String> {
-        let place = format!("{}{path}", self.unicode);
+        self.load_text(&*format!("{}{path}", self.unicode))
+    }
+
+    fn load_text_emoji(&self, file: &str) -> anyhow::Result<String> {
+        self.load_text_unicode(&*format!("{}{file}", self.emoji_base))
+            .context("emoji data not found; is `--emoji-base` correct?")
+    }
 
-        if place.starts_with("http://") || place.starts_with("https://") {
+    /// Load a text file from either a URL or a filesystem path.
+    fn load_text(&self, place: &str) -> anyhow::Result<String> {
+        if is_url(place) {
+            anyhow::ensure!(!self.offline, "cannot download <{place}> offline");
             self.agent
-                .get(&*place)
+                .get(place)
                 .call()
                 .map_err(anyhow::Error::new)
                 .and_then(|res| Ok(res.into_string()?))
                 .with_context(|| format!("failed to download file <{place}>"))
         } else {
-            fs::read_to_string(&*place).with_context(|| format!("failed to read in file {place}"))
+            fs::read_to_string(place).with_context(|| format!("failed to read in file {place}"))
         }
     }
 
-    fn write_ron(&self, relative_path: &str, items: Items) -> anyhow::Result<()> {
+    /// Append an alias section containing the given unescaped aliases to a name.
+    fn push_aliases(&self, name: &mut String, aliases: &str) {
+        let (before, after) = &self.alias_markup;
+        with_glib_markup_escaped(aliases, |aliases| {
+            name.push_str(" (");
+            name.push_str(before);
+            name.push_str(aliases);
+            name.push_str(after);
+            name.push(')');
+        });
+    }
+
+    fn write_ron(&self, relative_path: &str, value: impl Serialize) -> anyhow::Result<()> {
         let config = ron::ser::PrettyConfig::new().indentor("\t".to_owned());
-        let ron = ron::ser::to_string_pretty(&items, config).unwrap();
+        let ron = ron::ser::to_string_pretty(&value, config).unwrap();
 
         let path = self.out_dir.join(relative_path);
 
@@ -70,14 +476,88 @@ impl Opts<'_> {
         fs::write(&*path, ron).with_context(|| format!("failed to write to {}", path.display()))?;
 
         println!("Successfully wrote to {relative_path}");
+        self.written.borrow_mut().push(relative_path.to_owned());
 
         Ok(())
     }
 }
 
-struct Items {
-    extends: Vec<String>,
-    direct: Vec<Item>,
+#[cfg(test)]
+impl<'a> Opts<'a> {
+    /// The options of `cargo dev generate` without any flags,
+    /// except that every file is read from and written to `dir` and nothing is downloaded.
+    fn for_tests(dir: &'a Path) -> Self {
+        Self {
+            agent: ureq::agent(),
+            unicode: format!("{}/", dir.display()),
+            emoji_base: String::new(),
+            out_dir: dir,
+            duplicates: DuplicatePolicy::Skip,
+            cldr_annotations: None,
+            alias_markup: ("<small>".to_owned(), "</small>".to_owned()),
+            east_asian_width: false,
+            block_separators: false,
+            hangul_syllables: false,
+            emoji_codepoints: false,
+            emoji_flat: false,
+            no_deprecated: false,
+            font: None,
+            offline: true,
+            written: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// Write files into a new temporary directory, for generating from.
+#[cfg(test)]
+fn temp_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "rofi-unicode-dev-test-{}-{name}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&*dir);
+    fs::create_dir_all(&*dir).unwrap();
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+/// The item of a code point, as generated from `UnicodeData.txt`, for generating from.
+#[cfg(test)]
+fn codepoint_item(code_point: u32, name: &str) -> ucd::CodePointItem<'static> {
+    let c = char::from_u32(code_point).unwrap();
+    ucd::CodePointItem {
+        code_point: ucd::CodePoint(code_point),
+        general_category: "Lo",
+        bidi_class: "L",
+        item: Item {
+            name: format!("U+{code_point:04X}\t{c}\t{name}"),
+            search_aliases: Vec::new(),
+            content: Content::Text(c.to_string()),
+        },
+    }
+}
+
+/// A font that limits the generated characters to those it can display.
+struct Font {
+    path: String,
+    data: Vec<u8>,
+}
+
+impl Font {
+    fn face(&self) -> anyhow::Result<ttf_parser::Face<'_>> {
+        ttf_parser::Face::from_slice(&*self.data, 0)
+            .with_context(|| format!("failed to parse font {}", self.path))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Items {
+    pub(crate) extends: Vec<String>,
+    pub(crate) direct: Vec<Item>,
 }
 
 impl Items {
@@ -87,16 +567,81 @@ impl Items {
             direct,
         }
     }
+
+    /// Replace every submenu that contains exactly one item with that item, recursively,
+    /// since such a submenu only adds an extra step to reach the item.
+    ///
+    /// Submenus with `extends` are kept, as their items are only known once they are resolved.
+    fn flatten_single_item_submenus(&mut self) {
+        for item in &mut self.direct {
+            if let Content::Items(items) = &mut item.content {
+                items.flatten_single_item_submenus();
+                if items.extends.is_empty() && items.direct.len() == 1 {
+                    *item = items.direct.pop().unwrap();
+                }
+            }
+        }
+    }
 }
 
-struct Item {
-    name: String,
-    content: Content,
+#[derive(Clone)]
+pub(crate) struct Item {
+    pub(crate) name: String,
+    /// Extra text the item can be found by that is not displayed.
+    pub(crate) search_aliases: Vec<String>,
+    pub(crate) content: Content,
 }
 
-enum Content {
+#[derive(Clone)]
+pub(crate) enum Content {
     Text(String),
     Items(Items),
+    Separator,
+}
+
+/// A list of items that never contains two text items that copy the same text.
+struct Deduplicated {
+    policy: DuplicatePolicy,
+    items: Vec<Item>,
+    /// Map from the text of each text item to its index in `items`.
+    texts: HashMap<String, usize>,
+}
+
+impl Deduplicated {
+    fn new(policy: DuplicatePolicy) -> Self {
+        Self {
+            policy,
+            items: Vec::new(),
+            texts: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, item: Item) {
+        if let Content::Text(text) = &item.content {
+            match self.texts.entry(text.clone()) {
+                hash_map::Entry::Occupied(entry) => {
+                    let existing = &mut self.items[*entry.get()];
+                    eprintln!(
+                        "warning: {:?} and {:?} both copy {text:?}",
+                        existing.name, item.name
+                    );
+                    if let DuplicatePolicy::Merge = self.policy {
+                        existing.name.push_str(" | ");
+                        existing.name.push_str(&*item.name);
+                    }
+                    return;
+                }
+                hash_map::Entry::Vacant(entry) => {
+                    entry.insert(self.items.len());
+                }
+            }
+        }
+        self.items.push(item);
+    }
+
+    fn into_items(self) -> Items {
+        Items::from_direct(self.items)
+    }
 }
 
 impl Serialize for Items {
@@ -104,7 +649,16 @@ impl Serialize for Items {
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("extends", &self.extends)?;
         for item in &self.direct {
-            map.serialize_entry(&item.name, &item.content)?;
+            match &item.content {
+                Content::Text(text) if !item.search_aliases.is_empty() => {
+                    let value = AliasedText {
+                        text,
+                        search_aliases: &*item.search_aliases,
+                    };
+                    map.serialize_entry(&item.name, &value)?;
+                }
+                content => map.serialize_entry(&item.name, content)?,
+            }
         }
         map.end()
     }
@@ -115,10 +669,104 @@ impl Serialize for Content {
         match self {
             Self::Text(s) => s.serialize(serializer),
             Self::Items(items) => items.serialize(serializer),
+            Self::Separator => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("separator", &true)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl Items {
+    /// Parse a map of items the way `rofi-unicode` reads it.
+    pub(crate) fn parse(ron: &str) -> anyhow::Result<Self> {
+        config::parse_file_items(ron).map(Self::from)
+    }
+}
+
+impl From<config::FileItems> for Items {
+    fn from(items: config::FileItems) -> Self {
+        let direct = items
+            .items
+            .into_iter()
+            .map(|item| Item {
+                name: item.name,
+                search_aliases: item.search_aliases,
+                content: match item.content {
+                    config::FileContent::Text(text) => Content::Text(text),
+                    config::FileContent::Items(items) => Content::Items(Self::from(items)),
+                    config::FileContent::Separator => Content::Separator,
+                },
+            })
+            .collect();
+        Self {
+            extends: items.extends,
+            direct,
         }
     }
 }
 
+/// Text to copy along with its search aliases, serialized as a map with the reserved `text` key.
+struct AliasedText<'a> {
+    text: &'a str,
+    search_aliases: &'a [String],
+}
+
+impl Serialize for AliasedText<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("text", self.text)?;
+        map.serialize_entry("search_aliases", self.search_aliases)?;
+        map.end()
+    }
+}
+
+fn is_url(place: &str) -> bool {
+    place.starts_with("http://") || place.starts_with("https://")
+}
+
+/// Separator between the individual aliases in a name's alias section.
+const ALIAS_SEPARATOR: &str = " | ";
+
+/// Split a markup template around its `{}` placeholder,
+/// checking that the template has exactly one placeholder and that its tags are balanced.
+fn parse_markup_template(template: &str) -> anyhow::Result<(String, String)> {
+    let (before, after) = template
+        .split_once("{}")
+        .context("template does not contain {}")?;
+    anyhow::ensure!(
+        !after.contains("{}"),
+        "template contains more than one {{}}"
+    );
+
+    let without_placeholder = format!("{before}{after}");
+    let mut open_tags = Vec::new();
+    let mut rest = &*without_placeholder;
+    while let Some((_, tag)) = rest.split_once('<') {
+        let (tag, after_tag) = tag.split_once('>').context("unterminated tag")?;
+        rest = after_tag;
+
+        if let Some(closing) = tag.strip_prefix('/') {
+            let opening = open_tags
+                .pop()
+                .with_context(|| format!("unexpected closing tag </{closing}>"))?;
+            anyhow::ensure!(
+                opening == closing.trim(),
+                "tag <{opening}> closed by </{closing}>"
+            );
+        } else if !tag.ends_with('/') {
+            let name = tag.split_whitespace().next().context("empty tag")?;
+            open_tags.push(name.to_owned());
+        }
+    }
+    if let Some(unclosed) = open_tags.pop() {
+        anyhow::bail!("tag <{unclosed}> is never closed");
+    }
+
+    Ok((before.to_owned(), after.to_owned()))
+}
+
 fn with_glib_markup_escaped<O>(s: &str, f: impl FnOnce(&str) -> O) -> O {
     let escaped = unsafe { glib_sys::g_markup_escape_text(s.as_ptr().cast(), s.len() as isize) };
     let escaped_str = unsafe { CStr::from_ptr(escaped) }.to_str().unwrap();
@@ -137,3 +785,126 @@ impl<F: FnOnce()> Drop for Defer<F> {
         self.function.take().unwrap()();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_item(name: &str, text: &str) -> Item {
+        Item {
+            name: name.to_owned(),
+            search_aliases: Vec::new(),
+            content: Content::Text(text.to_owned()),
+        }
+    }
+
+    fn names(items: &Items) -> Vec<&str> {
+        items.direct.iter().map(|item| &*item.name).collect()
+    }
+
+    #[test]
+    fn duplicate_glyphs() {
+        // The first and last items both copy ❤
+        let colliding = [
+            text_item("U+2764\t❤\tHEAVY BLACK HEART", "❤"),
+            text_item("U+2665\t♥\tBLACK HEART SUIT", "♥"),
+            text_item("red heart", "❤"),
+        ];
+
+        let mut skipped = Deduplicated::new(DuplicatePolicy::Skip);
+        let mut merged = Deduplicated::new(DuplicatePolicy::Merge);
+        for item in colliding {
+            skipped.push(item.clone());
+            merged.push(item);
+        }
+
+        assert_eq!(
+            names(&skipped.into_items()),
+            [
+                "U+2764\t❤\tHEAVY BLACK HEART",
+                "U+2665\t♥\tBLACK HEART SUIT"
+            ],
+        );
+        assert_eq!(
+            names(&merged.into_items()),
+            [
+                "U+2764\t❤\tHEAVY BLACK HEART | red heart",
+                "U+2665\t♥\tBLACK HEART SUIT",
+            ],
+        );
+    }
+
+    #[test]
+    fn single_item_submenus() {
+        let submenu = |name: &str, items| Item {
+            name: name.to_owned(),
+            search_aliases: Vec::new(),
+            content: Content::Items(Items::from_direct(items)),
+        };
+        let mut items = Items::from_direct(vec![
+            submenu("one", vec![text_item("only", "1")]),
+            submenu(
+                "two",
+                vec![text_item("first", "1"), text_item("second", "2")],
+            ),
+            submenu(
+                "nested",
+                vec![submenu("inner", vec![text_item("deep", "3")])],
+            ),
+            Item {
+                name: "included".to_owned(),
+                search_aliases: Vec::new(),
+                content: Content::Items(Items {
+                    extends: vec!["codepoints.ron".to_owned()],
+                    direct: vec![text_item("extra", "4")],
+                }),
+            },
+        ]);
+        items.flatten_single_item_submenus();
+
+        assert_eq!(names(&items), ["only", "two", "deep", "included"]);
+        match &items.direct[1].content {
+            Content::Items(two) => assert_eq!(names(two), ["first", "second"]),
+            _ => panic!("submenu of two items was flattened"),
+        }
+    }
+
+    #[test]
+    fn option_names_as_items() {
+        let items = Items::parse(
+            r#"{
+                "Words": {
+                    "name": "Name",
+                    "weight": "Weight",
+                    "search_aliases": { "literal": "Literal" },
+                    "separator": "Separator",
+                    "copy_template": "Copy template",
+                },
+                "Red heart": { "text": "❤", "name": "Heart", "search_aliases": ["love"] },
+                "Quoted": { "copy_template": "'{}'", "match_mode": Prefix, "Heart": "❤" },
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(names(&items), ["Words", "Heart", "Quoted"]);
+
+        let submenu = |item: &Item| match &item.content {
+            Content::Items(items) => items.clone(),
+            _ => panic!("{:?} is not a submenu", item.name),
+        };
+        let words = submenu(&items.direct[0]);
+        assert_eq!(
+            names(&words),
+            [
+                "name",
+                "weight",
+                "search_aliases",
+                "separator",
+                "copy_template"
+            ],
+        );
+        assert_eq!(names(&submenu(&words.direct[2])), ["literal"]);
+
+        assert_eq!(items.direct[1].search_aliases, ["love"]);
+        assert_eq!(names(&submenu(&items.direct[2])), ["Heart"]);
+    }
+}
@@ -0,0 +1,69 @@
+//! Generation of Nerd Fonts icons from its `glyphnames.json`.
+//!
+//! See <https://github.com/ryanoasis/nerd-fonts/blob/master/glyphnames.json>.
+
+use {
+    super::{with_glib_markup_escaped, Content, Item, Items, Opts},
+    ::{
+        anyhow::Context as _,
+        serde::Deserialize,
+        std::collections::{BTreeMap, HashMap},
+    },
+};
+
+pub(super) fn generate(opts: &Opts<'_>, place: &str) -> anyhow::Result<()> {
+    let glyph_names_json = opts.load_text(place)?;
+    // Sorted by name, so that the output doesn't depend on the order of the file.
+    let glyph_names = serde_json::from_str::<BTreeMap<String, GlyphNamesEntry>>(&*glyph_names_json)
+        .with_context(|| format!("failed to parse {place}"))?;
+
+    // Prefixes such as `fa` and `md`, in order
+    let mut prefixes = Vec::<(&str, Vec<Item>)>::new();
+    let mut prefix_indices = HashMap::<&str, usize>::new();
+
+    for (name, entry) in &glyph_names {
+        let code = match entry {
+            GlyphNamesEntry::Glyph { code } => code,
+            // Such as `METADATA`
+            GlyphNamesEntry::Other(_) => continue,
+        };
+        let glyph = u32::from_str_radix(code, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .with_context(|| format!("icon {name} has invalid code point {code:?}"))?;
+
+        // Private use characters have no name in UnicodeData.txt, so only the icon name is shown.
+        let item = Item {
+            name: with_glib_markup_escaped(&*format!("{glyph}\tnf-{name}"), |s| s.to_owned()),
+            search_aliases: Vec::new(),
+            content: Content::Text(glyph.to_string()),
+        };
+
+        let prefix = name.split_once('-').map_or(&**name, |(prefix, _)| prefix);
+        let index = *prefix_indices.entry(prefix).or_insert_with(|| {
+            prefixes.push((prefix, Vec::new()));
+            prefixes.len() - 1
+        });
+        prefixes[index].1.push(item);
+    }
+
+    let items = prefixes
+        .into_iter()
+        .map(|(prefix, items)| Item {
+            name: with_glib_markup_escaped(prefix, |s| s.to_owned()),
+            search_aliases: Vec::new(),
+            content: Content::Items(Items::from_direct(items)),
+        })
+        .collect();
+
+    opts.write_ron("nerdfonts.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GlyphNamesEntry {
+    Glyph { code: String },
+    Other(serde::de::IgnoredAny),
+}
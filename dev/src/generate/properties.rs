@@ -0,0 +1,111 @@
+//! Grouping of characters by the binary properties listed in `PropList.txt`.
+//!
+//! See <http://www.unicode.org/reports/tr44/#PropList.txt>.
+
+use {
+    super::{
+        ucd::{CodePointItem, CodePointRange, UnicodeData},
+        Content, Deduplicated, Item, Items, Opts,
+    },
+    ::serde::Deserialize,
+};
+
+pub(super) const PROP_LIST: &str = "Public/UCD/latest/ucd/PropList.txt";
+
+pub(super) fn generate(
+    opts: &Opts<'_>,
+    data: &UnicodeData<'_>,
+    codepoints: &[CodePointItem<'_>],
+) -> anyhow::Result<()> {
+    let ranges = data
+        .prop_list
+        .iter()
+        .map(|line| (line.property, &line.range));
+    let properties = group_by_ranges(ranges, codepoints);
+
+    let items = properties
+        .into_iter()
+        .map(|(property, indices)| {
+            let mut items = Deduplicated::new(opts.duplicates);
+            for i in indices {
+                items.push(codepoints[i].item.clone());
+            }
+            Item {
+                name: property.replace('_', " "),
+                search_aliases: Vec::new(),
+                content: Content::Items(items.into_items()),
+            }
+        })
+        .collect();
+
+    opts.write_ron("properties.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+/// Group the items of code points by the named ranges of a UCD file, such as properties,
+/// returning each name along with the indices into `codepoints` of the items in its ranges.
+///
+/// Groups are in order of their first code point, and so are the items of each group.
+/// Groups without any items are left at the end, in the order they are first named.
+pub(super) fn group_by_ranges<'a>(
+    ranges: impl IntoIterator<Item = (&'a str, &'a CodePointRange)>,
+    codepoints: &[CodePointItem<'_>],
+) -> Vec<(&'a str, Vec<usize>)> {
+    let mut groups = Vec::<(&str, Vec<usize>)>::new();
+
+    for (name, range) in ranges {
+        let index = match groups.iter().position(|(group, _)| *group == name) {
+            Some(index) => index,
+            None => {
+                groups.push((name, Vec::new()));
+                groups.len() - 1
+            }
+        };
+        let indices = &mut groups[index].1;
+
+        for code_point in range.iter() {
+            // Code points without an item, such as those that are unassigned, are skipped.
+            if let Ok(i) = codepoints.binary_search_by_key(&code_point, |item| item.code_point) {
+                indices.push(i);
+            }
+        }
+    }
+
+    // `codepoints` is sorted, so sorting indices into it puts items in order of code point.
+    for (_, indices) in &mut groups {
+        indices.sort_unstable();
+        indices.dedup();
+    }
+    // The sort is stable, so groups without any items stay in order.
+    groups.sort_by_key(|(_, indices)| indices.first().copied().unwrap_or(usize::MAX));
+
+    groups
+}
+
+/// A line of `PropList.txt`.
+#[derive(Deserialize)]
+pub(super) struct PropListLine<'a> {
+    pub(super) range: CodePointRange,
+    pub(super) property: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::de_ucd, PropListLine};
+
+    #[test]
+    fn range_of_ten_code_points() {
+        let file = "0030..0039    ; ASCII_Hex_Digit # Nd  [10] DIGIT ZERO..DIGIT NINE\n";
+        let line = de_ucd::lines::<PropListLine<'_>>(file)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(line.property, "ASCII_Hex_Digit");
+        let code_points = line.range.iter().map(|code_point| code_point.0);
+        assert_eq!(
+            code_points.collect::<Vec<_>>(),
+            (0x30..=0x39).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Grouping of characters by the scripts listed in `Scripts.txt`.
+//!
+//! See <http://www.unicode.org/reports/tr24/>.
+
+use {
+    super::{
+        de_ucd, properties,
+        ucd::{CodePointItem, CodePointRange},
+        Content, Deduplicated, Item, Items, Opts,
+    },
+    ::{anyhow::Context as _, serde::Deserialize},
+};
+
+pub(super) const SCRIPTS: &str = "Public/UCD/latest/ucd/Scripts.txt";
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let scripts_txt = opts.load_text_unicode(SCRIPTS)?;
+    let scripts = de_ucd::lines::<ScriptsLine<'_>>(&*scripts_txt)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to parse {SCRIPTS}"))?;
+
+    let ranges = scripts.iter().map(|line| (line.script, &line.range));
+    let items = properties::group_by_ranges(ranges, codepoints)
+        .into_iter()
+        .filter(|(_, indices)| !indices.is_empty())
+        .map(|(script, indices)| {
+            let mut items = Deduplicated::new(opts.duplicates);
+            for i in indices {
+                items.push(codepoints[i].item.clone());
+            }
+            Item {
+                name: script.replace('_', " "),
+                search_aliases: Vec::new(),
+                content: Content::Items(items.into_items()),
+            }
+        })
+        .collect();
+
+    opts.write_ron("scripts.ron", Items::from_direct(items))?;
+
+    Ok(())
+}
+
+/// A line of `Scripts.txt`.
+#[derive(Deserialize)]
+struct ScriptsLine<'a> {
+    range: CodePointRange,
+    script: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            super::{codepoint_item, temp_dir},
+            *,
+        },
+        std::fs,
+    };
+
+    #[test]
+    fn code_point_order() {
+        let scripts_txt = "\
+            0370..0373    ; Greek # L&   [4] GREEK CAPITAL LETTER HETA..GREEK SMALL LETTER SAMPI\n\
+            03B1          ; Greek # L&       GREEK SMALL LETTER ALPHA\n\
+            00E9          ; Latin # L&       LATIN SMALL LETTER E WITH ACUTE\n\
+            0041..005A    ; Latin # L&  [26] LATIN CAPITAL LETTER A..LATIN CAPITAL LETTER Z\n\
+            10300..1031F  ; Old_Italic # Lo  [32] OLD ITALIC LETTER A..OLD ITALIC LETTER ESS\n";
+        let dir = temp_dir("scripts-code-point-order", &[(SCRIPTS, scripts_txt)]);
+        let codepoints = [
+            codepoint_item(0x41, "LATIN CAPITAL LETTER A"),
+            codepoint_item(0xE9, "LATIN SMALL LETTER E WITH ACUTE"),
+            codepoint_item(0x3B1, "GREEK SMALL LETTER ALPHA"),
+            codepoint_item(0x10300, "OLD ITALIC LETTER A"),
+        ];
+        generate(&Opts::for_tests(&*dir), &codepoints).unwrap();
+
+        let scripts_ron = fs::read_to_string(dir.join("scripts.ron")).unwrap();
+        let items = Items::parse(&scripts_ron).unwrap();
+        let scripts = items
+            .direct
+            .iter()
+            .map(|script| {
+                let items = match &script.content {
+                    Content::Items(items) => &items.direct,
+                    _ => panic!("{:?} is not a submenu", script.name),
+                };
+                let texts = items
+                    .iter()
+                    .map(|item| match &item.content {
+                        Content::Text(text) => &**text,
+                        _ => panic!("{:?} is not a text item", item.name),
+                    })
+                    .collect::<Vec<_>>();
+                (&*script.name, texts)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scripts,
+            [
+                ("Latin", vec!["A", "é"]),
+                ("Greek", vec!["α"]),
+                ("Old Italic", vec!["𐌀"]),
+            ],
+        );
+    }
+}
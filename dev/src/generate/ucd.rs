@@ -1,39 +1,147 @@
 use {
-    super::{de_ucd, with_glib_markup_escaped, Content, Item, Items, Opts},
+    super::{
+        de_ucd,
+        properties::{PropListLine, PROP_LIST},
+        with_glib_markup_escaped, Content, Deduplicated, Item, Opts, ALIAS_SEPARATOR,
+    },
     ::{
         anyhow::Context as _,
         serde::{
             de::{self, Deserializer, IntoDeserializer},
             Deserialize,
         },
-        std::fmt::{self, Display, Formatter, Write as _},
+        std::{
+            collections::{BTreeMap, HashMap, HashSet},
+            fmt::{self, Display, Formatter},
+            ops::Range,
+        },
     },
 };
 
-pub(super) fn generate(opts: &Opts<'_>) -> anyhow::Result<()> {
-    const UNICODE_DATA: &str = "Public/UCD/latest/ucd/UnicodeData.txt";
-    const NAME_ALIASES: &str = "Public/UCD/latest/ucd/NameAliases.txt";
+/// The contents of the UCD files needed to generate codepoint data.
+pub(super) struct Files {
+    unicode_data: String,
+    name_aliases: String,
+    /// Needed for which characters are deprecated, as well as for `properties.ron`.
+    prop_list: String,
+    /// Only loaded when padding glyphs to a common width.
+    east_asian_width: Option<String>,
+    /// Only loaded when generating Hangul syllables.
+    jamo: Option<String>,
+}
 
-    let unicode_data = opts.load_text_unicode(UNICODE_DATA)?;
-    let mut unicode_data = de_ucd::lines::<UnicodeDataLine<'_>>(&unicode_data)
-        .collect::<Result<Vec<_>, _>>()
-        .with_context(|| format!("failed to parse {UNICODE_DATA}"))?;
+pub(super) const UNICODE_DATA: &str = "Public/UCD/latest/ucd/UnicodeData.txt";
+pub(super) const NAME_ALIASES: &str = "Public/UCD/latest/ucd/NameAliases.txt";
+pub(super) const EAST_ASIAN_WIDTH: &str = "Public/UCD/latest/ucd/EastAsianWidth.txt";
+pub(super) const BLOCKS: &str = "Public/UCD/latest/ucd/Blocks.txt";
+pub(super) const JAMO: &str = "Public/UCD/latest/ucd/Jamo.txt";
 
-    let name_aliases = opts.load_text_unicode(NAME_ALIASES)?;
-    let mut name_aliases = de_ucd::lines::<NameAlias<'_>>(&name_aliases)
-        .collect::<Result<Vec<_>, _>>()
-        .with_context(|| format!("failed to parse {NAME_ALIASES}"))?;
+impl Files {
+    pub(super) fn load(opts: &Opts<'_>) -> anyhow::Result<Self> {
+        Ok(Self {
+            unicode_data: opts.load_text_unicode(UNICODE_DATA)?,
+            name_aliases: opts.load_text_unicode(NAME_ALIASES)?,
+            prop_list: opts.load_text_unicode(PROP_LIST)?,
+            east_asian_width: opts
+                .east_asian_width
+                .then(|| opts.load_text_unicode(EAST_ASIAN_WIDTH))
+                .transpose()?,
+            jamo: opts
+                .hangul_syllables
+                .then(|| {
+                    opts.load_text_unicode(JAMO)
+                        .context("Jamo.txt is needed to generate Hangul syllables")
+                })
+                .transpose()?,
+        })
+    }
 
-    // They're probably already sorted but we should just make sure
-    unicode_data.sort_unstable_by_key(|line| line.code_point);
-    name_aliases.sort_unstable_by_key(|line| line.code_point);
+    pub(super) fn parse(&self) -> anyhow::Result<UnicodeData<'_>> {
+        let mut unicode_data = de_ucd::lines::<UnicodeDataLine<'_>>(&self.unicode_data)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to parse {UNICODE_DATA}"))?;
 
-    let data = UnicodeData {
-        unicode_data,
-        name_aliases,
-    };
+        let mut name_aliases = de_ucd::lines::<NameAlias<'_>>(&self.name_aliases)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to parse {NAME_ALIASES}"))?;
+
+        let prop_list = de_ucd::lines::<PropListLine<'_>>(&self.prop_list)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to parse {PROP_LIST}"))?;
+
+        let mut east_asian_width = self
+            .east_asian_width
+            .as_deref()
+            .map(|file| {
+                de_ucd::lines::<EastAsianWidthLine<'_>>(file)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("failed to parse {EAST_ASIAN_WIDTH}"))
+            })
+            .transpose()?;
+
+        let jamo = self
+            .jamo
+            .as_deref()
+            .map(|file| {
+                de_ucd::lines::<JamoLine<'_>>(file)
+                    .map(|res| res.map(|line| (line.code_point, line.short_name)))
+                    .collect::<Result<HashMap<_, _>, _>>()
+                    .with_context(|| format!("failed to parse {JAMO}"))
+            })
+            .transpose()?;
+
+        // They're probably already sorted but we should just make sure
+        unicode_data.sort_unstable_by_key(|line| line.code_point);
+        // Stably, as the last correction of a code point is the one that applies
+        name_aliases.sort_by_key(|line| line.code_point);
+        if let Some(east_asian_width) = &mut east_asian_width {
+            east_asian_width.sort_unstable_by_key(|line| line.range.start);
+        }
 
-    opts.write_ron("codepoints.ron", generate_codepoints(&data)?)?;
+        Ok(UnicodeData {
+            unicode_data,
+            name_aliases,
+            prop_list,
+            east_asian_width,
+            jamo,
+        })
+    }
+}
+
+pub(super) fn generate(opts: &Opts<'_>, codepoints: &[CodePointItem<'_>]) -> anyhow::Result<()> {
+    let blocks_txt = opts
+        .block_separators
+        .then(|| opts.load_text_unicode(BLOCKS))
+        .transpose()?;
+    let mut blocks = blocks_txt
+        .as_deref()
+        .map(|file| {
+            de_ucd::lines::<BlocksLine<'_>>(file)
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("failed to parse {BLOCKS}"))
+        })
+        .transpose()?
+        .unwrap_or_default();
+    blocks.sort_unstable_by_key(|line| line.range.start);
+    let mut blocks = blocks.iter().peekable();
+
+    let mut items = Deduplicated::new(opts.duplicates);
+    for codepoint in codepoints {
+        // Both are sorted, so the separator of each block is pushed before its first item.
+        let mut block = None;
+        while let Some(line) = blocks.next_if(|line| line.range.start <= codepoint.code_point) {
+            block = Some(line);
+        }
+        if let Some(block) = block {
+            items.push(Item {
+                name: with_glib_markup_escaped(block.name, |name| format!("── {name} ──")),
+                search_aliases: Vec::new(),
+                content: Content::Separator,
+            });
+        }
+        items.push(codepoint.item.clone());
+    }
+    opts.write_ron("codepoints.ron", items.into_items())?;
 
     Ok(())
 }
@@ -42,78 +150,344 @@ pub(super) fn generate(opts: &Opts<'_>) -> anyhow::Result<()> {
 /// Not to be confused with `UnicodeData.txt`,
 /// whose data is contained in a specific field of this type.
 /// Blame Unicode for the poor naming, not me.
-struct UnicodeData<'a> {
+pub(super) struct UnicodeData<'a> {
     unicode_data: Vec<UnicodeDataLine<'a>>,
     name_aliases: Vec<NameAlias<'a>>,
+    pub(super) prop_list: Vec<PropListLine<'a>>,
+    east_asian_width: Option<Vec<EastAsianWidthLine<'a>>>,
+    /// Map from each jamo to its short name.
+    jamo: Option<HashMap<CodePoint, &'a str>>,
+}
+
+/// The generated item of a single code point, along with the data it was generated from.
+pub(super) struct CodePointItem<'a> {
+    pub(super) code_point: CodePoint,
+    pub(super) general_category: &'a str,
+    pub(super) bidi_class: &'a str,
+    pub(super) item: Item,
 }
 
-fn generate_codepoints(data: &UnicodeData<'_>) -> anyhow::Result<Items> {
+/// Generate an item for every code point that has a name, in order of code point.
+pub(super) fn codepoint_items<'a>(
+    opts: &Opts<'_>,
+    data: &UnicodeData<'a>,
+) -> anyhow::Result<Vec<CodePointItem<'a>>> {
     let mut items = Vec::new();
 
-    let mut name_aliases = data.name_aliases.iter().fuse().peekable();
-    for &UnicodeDataLine {
-        code_point, name, ..
-    } in &data.unicode_data
+    let face = opts.font.as_ref().map(|font| font.face()).transpose()?;
+    let mut without_glyph = 0;
+
+    let deprecated = data
+        .prop_list
+        .iter()
+        .filter(|line| line.property == "Deprecated")
+        .flat_map(|line| line.range.iter())
+        .collect::<HashSet<_>>();
+
+    // Removed as they are used, so that aliases of code points that aren't generated are left
+    let mut name_aliases = BTreeMap::<CodePoint, Vec<&NameAlias<'_>>>::new();
+    for alias in &data.name_aliases {
+        name_aliases
+            .entry(alias.code_point)
+            .or_default()
+            .push(alias);
+    }
+
+    // UnicodeData.txt lists Hangul syllables as a range, since their names are derived,
+    // so the line starting the range stands for every syllable in it.
+    let lines = data.unicode_data.iter().flat_map(|line| {
+        let code_points = match &data.jamo {
+            Some(jamo) if line.name == "<Hangul Syllable, First>" => HANGUL_SYLLABLES
+                .map(|code_point| {
+                    let code_point = CodePoint(code_point);
+                    (code_point, Some(hangul_syllable_name(jamo, code_point)))
+                })
+                .collect(),
+            _ => vec![(line.code_point, None)],
+        };
+        code_points
+            .into_iter()
+            .map(move |(code_point, derived_name)| (code_point, derived_name, line))
+    });
+
+    for (
+        code_point,
+        derived_name,
+        &UnicodeDataLine {
+            name,
+            general_category,
+            bidi_class,
+            unicode_1_name,
+            iso_comment,
+            ..
+        },
+    ) in lines
     {
-        if let Some(next_alias) = name_aliases.peek() {
-            anyhow::ensure!(
-                next_alias.code_point >= code_point,
-                "NameAlias.txt contains code point U+{code_point} not in UnicodeData.txt",
-            );
-        }
+        let derived_name = derived_name.transpose()?;
 
         // Name with Unicode corrections applied, if there are any
-        let mut corrected_name = name;
+        let mut corrected_name = derived_name.as_deref().unwrap_or(name);
         let mut alternate_names = String::new();
-        const ALTERNATE_NAME_SEPARATOR: &str = " | ";
 
-        while let Some(alias) = name_aliases.next_if(|alias| alias.code_point == code_point) {
+        for alias in name_aliases.remove(&code_point).unwrap_or_default() {
             match alias.ty {
                 AliasType::Correction | AliasType::Control | AliasType::Figment => {
                     corrected_name = alias.value;
                 }
                 AliasType::Alternate | AliasType::Abbreviation => {
                     if !alternate_names.is_empty() {
-                        alternate_names.push_str(ALTERNATE_NAME_SEPARATOR);
+                        alternate_names.push_str(ALIAS_SEPARATOR);
                     }
                     alternate_names.push_str(alias.value);
                 }
             }
         }
 
-        if corrected_name.starts_with('<') {
+        // The Unicode 1.0 name is often the more familiar one, such as for controls
+        if !unicode_1_name.is_empty()
+            && unicode_1_name != corrected_name
+            && !alternate_names
+                .split(ALIAS_SEPARATOR)
+                .any(|alias| alias == unicode_1_name)
+        {
+            if !alternate_names.is_empty() {
+                alternate_names.push_str(ALIAS_SEPARATOR);
+            }
+            alternate_names.push_str(unicode_1_name);
+        }
+
+        // The ISO 10646 comment, which only a handful of characters have
+        if !iso_comment.is_empty() {
+            if !alternate_names.is_empty() {
+                alternate_names.push_str(ALIAS_SEPARATOR);
+            }
+            alternate_names.push_str(iso_comment);
+        }
+
+        if let Some(dots) = braille_dots(code_point) {
+            if !alternate_names.is_empty() {
+                alternate_names.push_str(ALIAS_SEPARATOR);
+            }
+            alternate_names.push_str(&*dots);
+        }
+
+        // Characters that silently reorder the text around them, such as in Trojan Source attacks
+        if is_explicit_bidi(bidi_class) {
+            if !alternate_names.is_empty() {
+                alternate_names.push_str(ALIAS_SEPARATOR);
+            }
+            alternate_names.push_str("bidirectional control");
+        }
+
+        if deprecated.contains(&code_point) {
+            if opts.no_deprecated {
+                continue;
+            }
+            if !alternate_names.is_empty() {
+                alternate_names.push_str(ALIAS_SEPARATOR);
+            }
+            alternate_names.push_str("deprecated");
+        }
+
+        if corrected_name.starts_with('<') || is_noncharacter(code_point) {
             continue;
         }
+        // Surrogates are not scalar values and so can't be copied
         let scalar_value = match char::from_u32(code_point.0) {
             Some(scalar_value) => scalar_value,
             None => continue,
         };
 
-        let printable = if scalar_value.is_control() {
-            ' '
-        } else {
-            scalar_value
-        };
+        if let Some(face) = &face {
+            // Controls and separators are never drawn by themselves, so they can't be tofu.
+            let drawn = !general_category.starts_with(['C', 'Z']);
+            if drawn && face.glyph_index(scalar_value).is_none() {
+                without_glyph += 1;
+                continue;
+            }
+        }
+
+        let mut printable = printable(scalar_value, general_category);
+        if let Some(east_asian_width) = &data.east_asian_width {
+            if !is_wide(east_asian_width, code_point) {
+                printable.push(' ');
+            }
+        }
 
         let name_unescaped = format!("U+{code_point}\t{printable}\t{corrected_name}");
         let mut name = with_glib_markup_escaped(&*name_unescaped, |s| s.to_owned());
         if !alternate_names.is_empty() {
-            with_glib_markup_escaped(&*alternate_names, |alternate_names| {
-                write!(name, " (<small>{alternate_names}</small>)").unwrap();
-            });
+            opts.push_aliases(&mut name, &*alternate_names);
         }
 
-        items.push(Item {
-            name,
-            content: Content::Text(scalar_value.to_string()),
+        let mut search_aliases = math_alphanumeric_alias(code_point, corrected_name)
+            .into_iter()
+            .collect::<Vec<_>>();
+        if is_strong_rtl(bidi_class) {
+            search_aliases.push("right-to-left".to_owned());
+        }
+
+        items.push(CodePointItem {
+            code_point,
+            general_category,
+            bidi_class,
+            item: Item {
+                name,
+                search_aliases,
+                content: Content::Text(scalar_value.to_string()),
+            },
         });
     }
 
-    if let Some(extra_alias) = name_aliases.next() {
-        anyhow::bail!("unexpected extra alias for U+{}", extra_alias.code_point);
+    // Such as aliases of code points within the ranges of UnicodeData.txt, which are never listed
+    for (code_point, aliases) in name_aliases {
+        let values = aliases.iter().map(|alias| alias.value).collect::<Vec<_>>();
+        eprintln!(
+            "warning: dropping aliases of U+{code_point}, which is not in UnicodeData.txt: {}",
+            values.join(ALIAS_SEPARATOR),
+        );
+    }
+
+    if let Some(font) = &opts.font {
+        println!(
+            "Left out {without_glyph} characters without a glyph in {}",
+            font.path
+        );
+    }
+
+    Ok(items)
+}
+
+/// The plain letter or digit of a Mathematical Alphanumeric Symbol along with its style,
+/// such as `double-struck A` for U+1D538 MATHEMATICAL DOUBLE-STRUCK CAPITAL A.
+fn math_alphanumeric_alias(code_point: CodePoint, name: &str) -> Option<String> {
+    if !(0x1D400..=0x1D7FF).contains(&code_point.0) {
+        return None;
+    }
+    let rest = name.strip_prefix("MATHEMATICAL ")?;
+    let (style, kind, base) = ["CAPITAL", "SMALL", "DIGIT"].iter().find_map(|&kind| {
+        let (style, base) = rest.split_once(&*format!(" {kind} "))?;
+        Some((style, kind, base))
+    })?;
+
+    let base = match kind {
+        "DIGIT" => {
+            let digits = [
+                "ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE",
+            ];
+            let digit = digits.iter().position(|&digit| digit == base)?;
+            digit.to_string()
+        }
+        "CAPITAL" => {
+            let base = base.to_lowercase();
+            let mut chars = base.chars();
+            let first = chars.next()?;
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => base.to_lowercase(),
+    };
+
+    Some(format!("{} {base}", style.to_lowercase()))
+}
+
+/// The raised dots of a braille pattern, such as `dots-14` for U+2809.
+///
+/// Bit `n` of the low byte of a braille pattern's code point is set if dot `n + 1` is raised.
+fn braille_dots(code_point: CodePoint) -> Option<String> {
+    if !(0x2801..=0x28FF).contains(&code_point.0) {
+        return None;
+    }
+    let dots = (0..8)
+        .filter(|bit| code_point.0 & (1 << bit) != 0)
+        .map(|bit| char::from(b'1' + bit as u8))
+        .collect::<String>();
+    Some(format!("dots-{dots}"))
+}
+
+/// Whether a code point is permanently reserved for internal use.
+///
+/// See <https://www.unicode.org/faq/private_use.html#noncharacters>.
+fn is_noncharacter(code_point: CodePoint) -> bool {
+    (0xFDD0..=0xFDEF).contains(&code_point.0) || code_point.0 & 0xFFFE == 0xFFFE
+}
+
+/// The text to display for a character in the glyph column.
+///
+/// Characters without any visible rendering of their own are shown as a space,
+/// so that they neither display as tofu nor disturb the layout of the row.
+/// Combining marks are shown on a dotted circle,
+/// as otherwise they would combine with the preceding tab.
+fn printable(scalar_value: char, general_category: &str) -> String {
+    match general_category {
+        // Controls, format controls and line and paragraph separators
+        "Cc" | "Cf" | "Zl" | "Zp" => " ".to_owned(),
+        // Nonspacing, spacing and enclosing marks
+        "Mn" | "Mc" | "Me" => format!("\u{25CC}{scalar_value}"),
+        _ => scalar_value.to_string(),
+    }
+}
+
+/// Whether a code point takes up two columns in a monospace font,
+/// given the sorted lines of `EastAsianWidth.txt`.
+/// Code points not listed in the file are neutral, and so narrow.
+fn is_wide(east_asian_width: &[EastAsianWidthLine<'_>], code_point: CodePoint) -> bool {
+    let index = east_asian_width.partition_point(|line| line.range.start <= code_point);
+    let line = match index.checked_sub(1) {
+        Some(index) => &east_asian_width[index],
+        None => return false,
+    };
+    line.range.end.unwrap_or(line.range.start) >= code_point && matches!(line.width, "W" | "F")
+}
+
+const HANGUL_SYLLABLES: Range<u32> = 0xAC00..0xD7A4;
+
+/// Derive the name of a Hangul syllable from the short names of its jamo.
+///
+/// See section 3.12 of the Unicode Standard, "Conjoining Jamo Behavior".
+fn hangul_syllable_name(
+    jamo: &HashMap<CodePoint, &str>,
+    code_point: CodePoint,
+) -> anyhow::Result<String> {
+    const LEADING_START: u32 = 0x1100;
+    const VOWEL_START: u32 = 0x1161;
+    // The first trailing jamo is U+11A8, as an index of 0 means there isn't one.
+    const TRAILING_START: u32 = 0x11A7;
+    const VOWELS: u32 = 21;
+    const TRAILINGS: u32 = 28;
+
+    let index = code_point.0 - HANGUL_SYLLABLES.start;
+    let leading = LEADING_START + index / (VOWELS * TRAILINGS);
+    let vowel = VOWEL_START + index % (VOWELS * TRAILINGS) / TRAILINGS;
+    let trailing = TRAILING_START + index % TRAILINGS;
+
+    let mut name = "HANGUL SYLLABLE ".to_owned();
+    for jamo_code_point in [leading, vowel, trailing] {
+        if jamo_code_point == TRAILING_START {
+            continue;
+        }
+        let jamo_code_point = CodePoint(jamo_code_point);
+        let short_name = jamo
+            .get(&jamo_code_point)
+            .with_context(|| format!("{JAMO} has no short name for U+{jamo_code_point}"))?;
+        name.push_str(short_name);
     }
+    Ok(name)
+}
+
+/// Whether a bidi class is one of the explicit embeddings, overrides and isolates,
+/// which change the direction of the text that follows them.
+///
+/// See <https://www.unicode.org/reports/tr9/#Bidirectional_Character_Types>.
+pub(super) fn is_explicit_bidi(bidi_class: &str) -> bool {
+    matches!(
+        bidi_class,
+        "LRE" | "LRO" | "RLE" | "RLO" | "PDF" | "LRI" | "RLI" | "FSI" | "PDI"
+    )
+}
 
-    Ok(Items::from_direct(items))
+/// Whether a bidi class is strongly right-to-left, as in Hebrew and Arabic.
+pub(super) fn is_strong_rtl(bidi_class: &str) -> bool {
+    matches!(bidi_class, "R" | "AL")
 }
 
 /// A line of `UnicodeData.txt`.
@@ -123,16 +497,16 @@ fn generate_codepoints(data: &UnicodeData<'_>) -> anyhow::Result<Items> {
 struct UnicodeDataLine<'a> {
     code_point: CodePoint,
     name: &'a str,
-    _general_category: &'a str,
+    general_category: &'a str,
     _canonical_combining_class: &'a str,
-    _bidi_class: &'a str,
+    bidi_class: &'a str,
     _decomposition: &'a str,
     _decimal_value: de::IgnoredAny,
     _digit_value: &'a str,
     _numeric_value: &'a str,
     _bidi_mirrored: de::IgnoredAny,
-    _unicode_1_name: de::IgnoredAny,
-    _iso_comment: de::IgnoredAny,
+    unicode_1_name: &'a str,
+    iso_comment: &'a str,
     _simple_uppercase_mapping: de::IgnoredAny,
     _simple_lowercase_mapping: de::IgnoredAny,
     _simple_titlecase_mapping: de::IgnoredAny,
@@ -148,6 +522,33 @@ struct NameAlias<'a> {
     ty: AliasType,
 }
 
+/// A line of `Blocks.txt`.
+///
+/// See <http://www.unicode.org/reports/tr44/#Blocks.txt>.
+#[derive(Deserialize)]
+pub(super) struct BlocksLine<'a> {
+    pub(super) range: CodePointRange,
+    pub(super) name: &'a str,
+}
+
+/// A line of `Jamo.txt`.
+///
+/// See <http://www.unicode.org/reports/tr44/#Jamo.txt>.
+#[derive(Deserialize)]
+struct JamoLine<'a> {
+    code_point: CodePoint,
+    short_name: &'a str,
+}
+
+/// A line of `EastAsianWidth.txt`.
+///
+/// See <http://www.unicode.org/reports/tr44/#EastAsianWidth.txt>.
+#[derive(Deserialize)]
+struct EastAsianWidthLine<'a> {
+    range: CodePointRange,
+    width: &'a str,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "lowercase")]
 enum AliasType {
@@ -159,7 +560,7 @@ enum AliasType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct CodePoint(u32);
+pub(super) struct CodePoint(pub(super) u32);
 
 impl<'de> Deserialize<'de> for CodePoint {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
@@ -173,7 +574,7 @@ impl<'de> Deserialize<'de> for CodePoint {
                 let code_point = u32::from_str_radix(v, 16)
                     .map_err(|_| E::invalid_type(de::Unexpected::Str(v), &self))?;
 
-                if code_point >= 0x10FFFF {
+                if code_point > 0x10FFFF {
                     return Err(E::custom(format_args!(
                         "{code_point:04X} is not a valid code point"
                     )));
@@ -192,10 +593,19 @@ impl Display for CodePoint {
     }
 }
 
-struct CodePointRange {
+/// A code point or inclusive range of code points, written as `XXXX` or `XXXX..YYYY`.
+pub(super) struct CodePointRange {
     start: CodePoint,
     end: Option<CodePoint>,
 }
+
+impl CodePointRange {
+    pub(super) fn iter(&self) -> impl Iterator<Item = CodePoint> {
+        let end = self.end.unwrap_or(self.start);
+        (self.start.0..=end.0).map(CodePoint)
+    }
+}
+
 impl<'de> Deserialize<'de> for CodePointRange {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct Visitor;
@@ -225,3 +635,330 @@ impl<'de, E: de::Error> IntoDeserializer<'de, E> for BorrowedStr<'de> {
         de::value::BorrowedStrDeserializer::new(self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::temp_dir, *},
+        std::path::Path,
+    };
+
+    fn files(unicode_data: &str) -> Files {
+        Files {
+            unicode_data: unicode_data.to_owned(),
+            name_aliases: String::new(),
+            prop_list: String::new(),
+            east_asian_width: None,
+            jamo: None,
+        }
+    }
+
+    /// The names of the items generated from some files, along with the text they copy.
+    fn generate_names(opts: &Opts<'_>, files: &Files) -> Vec<(String, String)> {
+        let data = files.parse().unwrap();
+        codepoint_items(opts, &data)
+            .unwrap()
+            .into_iter()
+            .map(|codepoint| match codepoint.item.content {
+                Content::Text(text) => (codepoint.item.name, text),
+                _ => panic!("code point item is not text"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noncharacters_and_combining_marks() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files(
+            "\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;\n\
+            0301;COMBINING ACUTE ACCENT;Mn;230;NSM;;;;;N;;;;;\n\
+            D800;<Non Private Use High Surrogate, First>;Cs;0;L;;;;;N;;;;;\n\
+            FDD0;NONCHARACTER;Cn;0;L;;;;;N;;;;;\n\
+            1FFFF;NONCHARACTER;Cn;0;L;;;;;N;;;;;\n",
+        );
+        assert_eq!(
+            generate_names(&opts, &files),
+            [
+                (
+                    "U+0041\tA\tLATIN CAPITAL LETTER A".to_owned(),
+                    "A".to_owned()
+                ),
+                (
+                    "U+0301\t\u{25CC}\u{301}\tCOMBINING ACUTE ACCENT".to_owned(),
+                    "\u{301}".to_owned(),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn combining_acute_accent() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files("0301;COMBINING ACUTE ACCENT;Mn;230;NSM;;;;;N;;;;;\n");
+        let (name, text) = generate_names(&opts, &files).pop().unwrap();
+        // Shown on a dotted circle, but copied by itself
+        assert_eq!(name, "U+0301\t\u{25CC}\u{301}\tCOMBINING ACUTE ACCENT");
+        assert_eq!(text, "\u{301}");
+
+        assert_eq!(printable('\u{20DD}', "Me"), "\u{25CC}\u{20DD}");
+        assert_eq!(printable('\u{903}', "Mc"), "\u{25CC}\u{903}");
+        assert_eq!(printable('\u{B4}', "Sk"), "\u{B4}");
+    }
+
+    #[test]
+    fn east_asian_width() {
+        let mut opts = Opts::for_tests(Path::new("."));
+        opts.east_asian_width = true;
+        let mut files = files(
+            "\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;\n\
+            3042;HIRAGANA LETTER A;Lo;0;L;;;;;N;;;;;\n",
+        );
+        files.east_asian_width = Some(
+            "\
+            0041..005A     ; Na # Lu    [26] LATIN CAPITAL LETTER A..LATIN CAPITAL LETTER Z\n\
+            3041..3096     ; W  # Lo    [86] HIRAGANA LETTER SMALL A..HIRAGANA LETTER SMALL KE\n"
+                .to_owned(),
+        );
+
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        // The narrow glyph is padded to the width of the wide one.
+        assert_eq!(
+            names,
+            [
+                "U+0041\tA \tLATIN CAPITAL LETTER A",
+                "U+3042\tあ\tHIRAGANA LETTER A",
+            ],
+        );
+    }
+
+    #[test]
+    fn unicode_1_names() {
+        let opts = Opts::for_tests(Path::new("."));
+        let mut files = files(
+            "\
+            0007;<control>;Cc;0;BN;;;;;N;BELL;;;;\n\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;LATIN CAPITAL LETTER A;;;0061;\n\
+            00A6;BROKEN BAR;So;0;ON;;;;;N;BROKEN VERTICAL BAR;;;;\n",
+        );
+        files.name_aliases = "0007;ALERT;control\n0007;BEL;abbreviation\n".to_owned();
+
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "U+0007\t \tALERT (<small>BEL | BELL</small>)",
+                // Not repeated when it is the same as the current name
+                "U+0041\tA\tLATIN CAPITAL LETTER A",
+                "U+00A6\t¦\tBROKEN BAR (<small>BROKEN VERTICAL BAR</small>)",
+            ],
+        );
+    }
+
+    #[test]
+    fn braille_dot_numbers() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files("2809;BRAILLE PATTERN DOTS-14;So;0;L;;;;;N;;;;;\n");
+        let (name, _) = generate_names(&opts, &files).pop().unwrap();
+        assert_eq!(
+            name,
+            "U+2809\t⠉\tBRAILLE PATTERN DOTS-14 (<small>dots-14</small>)"
+        );
+
+        assert_eq!(braille_dots(CodePoint(0x28FF)).unwrap(), "dots-12345678");
+        assert_eq!(braille_dots(CodePoint(0x2800)), None);
+    }
+
+    #[test]
+    fn ampersand_without_markup() {
+        let config_ron = r#"Config(root: { "extends": ["codepoints.ron"] })"#;
+        let dir = temp_dir("ampersand-without-markup", &[("config.ron", config_ron)]);
+        let mut opts = Opts::for_tests(&*dir);
+        // As with `--no-markup`
+        opts.alias_markup = (String::new(), String::new());
+        let files = files("0026;AMPERSAND;Po;0;ON;;;;;N;;;;;\n");
+        let data = files.parse().unwrap();
+        generate(&opts, &*codepoint_items(&opts, &data).unwrap()).unwrap();
+
+        let (config, _) = rofi_unicode::config::read_timed(vec![dir], "config.ron").unwrap();
+        let item = &config.root.items[0];
+        assert_eq!(item.name, "U+0026\t&\tAMPERSAND");
+        assert!(item.name_attributes.is_empty());
+        assert!(matches!(&item.content, rofi_unicode::config::Content::Text(text) if text == "&"));
+    }
+
+    #[test]
+    fn math_alphanumeric_aliases() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files(
+            "\
+            1D400;MATHEMATICAL BOLD CAPITAL A;Lu;0;L;<font> 0041;;;;N;;;;;\n\
+            1D538;MATHEMATICAL DOUBLE-STRUCK CAPITAL A;Lu;0;L;<font> 0041;;;;N;;;;;\n\
+            1D7D8;MATHEMATICAL DOUBLE-STRUCK DIGIT ZERO;Nd;0;EN;<font> 0030;0;0;0;N;;;;;\n",
+        );
+        let data = files.parse().unwrap();
+        let aliases = codepoint_items(&opts, &data)
+            .unwrap()
+            .into_iter()
+            .map(|codepoint| codepoint.item.search_aliases)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            aliases,
+            [["bold A"], ["double-struck A"], ["double-struck 0"]],
+        );
+
+        assert_eq!(
+            math_alphanumeric_alias(CodePoint(0x1D44E), "MATHEMATICAL ITALIC SMALL A").unwrap(),
+            "italic a"
+        );
+        assert_eq!(
+            math_alphanumeric_alias(CodePoint(0x0041), "LATIN CAPITAL LETTER A"),
+            None
+        );
+    }
+
+    #[test]
+    fn iso_comment() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files(
+            "\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;\n\
+            00B7;MIDDLE DOT;Po;0;ON;;;;;N;;Georgian comma;;;\n",
+        );
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "U+0041\tA\tLATIN CAPITAL LETTER A",
+                "U+00B7\t·\tMIDDLE DOT (<small>Georgian comma</small>)",
+            ],
+        );
+    }
+
+    #[test]
+    fn control_aliases() {
+        let opts = Opts::for_tests(Path::new("."));
+        let mut files = files(
+            "\
+            0000;<control>;Cc;0;BN;;;;;N;NULL;;;;\n\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;\n",
+        );
+        files.name_aliases = "\
+            0000;NULL;control\n\
+            0000;NUL;abbreviation\n\
+            E0100;VS17;abbreviation\n"
+            .to_owned();
+
+        // The alias of a code point that isn't generated is dropped rather than an error.
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "U+0000\t \tNULL (<small>NUL</small>)",
+                "U+0041\tA\tLATIN CAPITAL LETTER A",
+            ],
+        );
+    }
+
+    #[test]
+    fn deprecated() {
+        let mut opts = Opts::for_tests(Path::new("."));
+        let mut files = files(
+            "\
+            0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;\n\
+            0149;LATIN SMALL LETTER N PRECEDED BY APOSTROPHE;Ll;0;L;<compat> 02BC 006E;;;;N;\
+            LATIN SMALL LETTER APOSTROPHE N;;;;\n",
+        );
+        files.prop_list = "0149          ; Deprecated # L&       \
+            LATIN SMALL LETTER N PRECEDED BY APOSTROPHE\n"
+            .to_owned();
+
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "U+0041\tA\tLATIN CAPITAL LETTER A",
+                "U+0149\tŉ\tLATIN SMALL LETTER N PRECEDED BY APOSTROPHE \
+                (<small>LATIN SMALL LETTER APOSTROPHE N | deprecated</small>)",
+            ],
+        );
+
+        // As with `--no-deprecated`
+        opts.no_deprecated = true;
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["U+0041\tA\tLATIN CAPITAL LETTER A"]);
+    }
+
+    #[test]
+    fn bidirectional_controls() {
+        let opts = Opts::for_tests(Path::new("."));
+        let files = files(
+            "\
+            05D0;HEBREW LETTER ALEF;Lo;0;R;;;;;N;;;;;\n\
+            202E;RIGHT-TO-LEFT OVERRIDE;Cf;0;RLO;;;;;N;;;;;\n",
+        );
+        let names = generate_names(&opts, &files)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        // Right-to-left text by itself is not flagged.
+        assert_eq!(
+            names,
+            [
+                "U+05D0\tא\tHEBREW LETTER ALEF",
+                "U+202E\t \tRIGHT-TO-LEFT OVERRIDE (<small>bidirectional control</small>)",
+            ],
+        );
+    }
+
+    #[test]
+    fn hangul_syllables() {
+        let mut opts = Opts::for_tests(Path::new("."));
+        opts.hangul_syllables = true;
+        let mut files = files(
+            "\
+            AC00;<Hangul Syllable, First>;Lo;0;L;;;;;N;;;;;\n\
+            D7A3;<Hangul Syllable, Last>;Lo;0;L;;;;;N;;;;;\n",
+        );
+        let leading = "G GG N D DD R M B BB S SS _ J JJ C K T P H";
+        let vowels = "A AE YA YAE EO E YEO YE O WA WAE OE YO U WEO WE WI YU EU YI I";
+        let trailing = "G GG GS N NJ NH D L LG LM LB LS LT LP LH M B BS S SS NG J C K T P H";
+        let mut jamo = String::new();
+        for (start, short_names) in [(0x1100, leading), (0x1161, vowels), (0x11A8, trailing)] {
+            for (i, short_name) in short_names.split(' ').enumerate() {
+                // The leading consonant ieung is silent, so its short name is empty.
+                let short_name = short_name.replace('_', "");
+                jamo.push_str(&*format!("{:04X}; {short_name}\n", start + i));
+            }
+        }
+        files.jamo = Some(jamo);
+
+        let names = generate_names(&opts, &files);
+        assert_eq!(names.len(), 11172);
+        assert_eq!(names[0].0, "U+AC00\t가\tHANGUL SYLLABLE GA");
+        assert_eq!(names[0].1, "가");
+        assert_eq!(names[1].0, "U+AC01\t각\tHANGUL SYLLABLE GAG");
+        // Without a leading consonant
+        assert_eq!(names[0x1944].0, "U+C544\t아\tHANGUL SYLLABLE A");
+        assert_eq!(names[11171].0, "U+D7A3\t힣\tHANGUL SYLLABLE HIH");
+    }
+}
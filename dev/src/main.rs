@@ -10,17 +10,23 @@ use ::{
     },
 };
 
+mod bench;
+mod diff;
 mod generate;
+mod stats;
 
 /// Helper for developing and installing rofi-unicode
 #[derive(Parser)]
 enum Args {
     Generate(generate::Args),
     Install(InstallArgs),
+    Stats(stats::Args),
+    Diff(diff::Args),
+    Bench(bench::Args),
 }
 
-/// Install `rofi-unicode` to the system.
-/// Often must be run as root.
+/// Install `rofi-unicode` to the system, or to the current user with `--user`.
+/// Installing to the system often must be run as root.
 /// Requires both the library to be built and the default configuration files to be generated.
 #[derive(Parser)]
 pub struct InstallArgs {
@@ -31,6 +37,19 @@ pub struct InstallArgs {
     /// The Cargo profile to install from.
     #[clap(long, default_value = "release")]
     profile: String,
+
+    /// Install to the current user's home directory instead of the system, ignoring `--rofi-prefix`.
+    ///
+    /// The library is put in `~/.local/lib/rofi`,
+    /// which Rofi does not scan by default:
+    /// it must be added to `$ROFI_PLUGIN_PATH`, supported by Rofi 1.7.0 and later.
+    /// The configuration files are put in `$XDG_CONFIG_HOME/rofi-unicode`.
+    #[clap(long)]
+    user: bool,
+
+    /// Print what would be copied where, without copying anything.
+    #[clap(long)]
+    dry_run: bool,
 }
 
 #[allow(clippy::redundant_closure)]
@@ -42,6 +61,9 @@ fn main() -> anyhow::Result<()> {
     match Args::parse() {
         Args::Generate(args) => generate::generate(args),
         Args::Install(args) => install(args),
+        Args::Stats(args) => stats::stats(args),
+        Args::Diff(args) => diff::diff(args),
+        Args::Bench(args) => bench::bench(args),
     }
 }
 
@@ -49,6 +71,8 @@ fn install(
     InstallArgs {
         rofi_prefix,
         profile,
+        user,
+        dry_run,
     }: InstallArgs,
 ) -> anyhow::Result<()> {
     let profile = if profile == "dev" {
@@ -58,9 +82,43 @@ fn install(
         profile
     };
 
-    let so_source = PathBuf::from_iter(["target", &*profile, "librofi_unicode.so"]);
-    let mut so_dest = rofi_prefix;
-    so_dest.extend(["lib", "rofi", "unicode.so"]);
+    let so_source = find_so(&*PathBuf::from_iter(["target", &*profile]))?;
+    let (so_dir, etc) = if user {
+        let home = PathBuf::from(env::var_os("HOME").context("$HOME is not set")?);
+        let config_home =
+            env::var_os("XDG_CONFIG_HOME").map_or_else(|| home.join(".config"), PathBuf::from);
+        (
+            home.join(".local/lib/rofi"),
+            config_home.join("rofi-unicode"),
+        )
+    } else {
+        let config_dirs = env::var_os("XDG_CONFIG_DIRS").unwrap_or_else(|| "/etc/xdg".into());
+        let config_dir = config_dirs
+            .as_bytes()
+            .split(|&byte| byte == b':')
+            .next()
+            .unwrap();
+        (
+            rofi_prefix.join("lib/rofi"),
+            Path::new(OsStr::from_bytes(config_dir)).join("rofi-unicode"),
+        )
+    };
+    let so_dest = so_dir.join("unicode.so");
+
+    if dry_run {
+        println!(
+            "would copy {} to {}",
+            so_source.display(),
+            so_dest.display()
+        );
+        println!("would copy etc to {}", etc.display());
+        return Ok(());
+    }
+
+    if user {
+        fs::create_dir_all(&*so_dir)
+            .with_context(|| format!("failed to create directory {}", so_dir.display()))?;
+    }
     fs::copy(&*so_source, &*so_dest).with_context(|| {
         format!(
             "failed to copy {} to {}",
@@ -68,14 +126,6 @@ fn install(
             so_dest.display()
         )
     })?;
-
-    let config_dirs = env::var_os("XDG_CONFIG_DIRS").unwrap_or_else(|| "/etc/xdg".into());
-    let config_dir = config_dirs
-        .as_bytes()
-        .split(|&byte| byte == b':')
-        .next()
-        .unwrap();
-    let etc = Path::new(OsStr::from_bytes(config_dir)).join("rofi-unicode");
     dircpy::CopyBuilder::new("etc", &*etc)
         .overwrite(true)
         .run()
@@ -83,3 +133,35 @@ fn install(
 
     Ok(())
 }
+
+/// Find the built shared object in a Cargo target directory.
+///
+/// Prefers `librofi_unicode.so`, falling back to the only `.so` file in the directory
+/// in case the library has been renamed.
+fn find_so(dir: &Path) -> anyhow::Result<PathBuf> {
+    let expected = dir.join("librofi_unicode.so");
+    if expected.exists() {
+        return Ok(expected);
+    }
+
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path = entry
+            .with_context(|| format!("failed to read {}", dir.display()))?
+            .path();
+        if path.extension() == Some(OsStr::new("so")) {
+            candidates.push(path);
+        }
+    }
+
+    match <[_; 1]>::try_from(candidates) {
+        Ok([so]) => Ok(so),
+        Err(candidates) if candidates.is_empty() => {
+            anyhow::bail!("no shared object found in {}", dir.display())
+        }
+        Err(candidates) => anyhow::bail!(
+            "multiple shared objects found in {}: {candidates:?}",
+            dir.display()
+        ),
+    }
+}
@@ -0,0 +1,121 @@
+use {
+    crate::generate::{Content, Items},
+    ::{
+        anyhow::Context as _,
+        clap::Parser,
+        std::{collections::BTreeMap, fs, path::PathBuf},
+    },
+};
+
+/// Print statistics about the generated configuration files in `./etc`.
+/// Fails if any of them has structural problems, such as an empty list.
+#[derive(Parser)]
+pub struct Args {
+    /// The directory the files were generated into.
+    #[clap(long, default_value = "etc")]
+    dir: PathBuf,
+
+    /// The generated files to inspect, relative to `--dir`.
+    #[clap(default_values = &["codepoints.ron", "emojis.ron"])]
+    files: Vec<String>,
+}
+
+pub(crate) fn stats(Args { dir, files }: Args) -> anyhow::Result<()> {
+    let mut problems = 0;
+
+    for file in files {
+        let path = dir.join(&*file);
+        let ron = fs::read_to_string(&*path)
+            .with_context(|| format!("failed to read file {}", path.display()))?;
+        let items = Items::parse(&ron)
+            .with_context(|| format!("failed to parse file {}", path.display()))?;
+
+        let mut stats = Stats::default();
+        stats.visit(&items, &mut Vec::new(), 0);
+
+        println!("{file}:");
+        println!("\ttotal entries: {}", stats.entries);
+        println!("\tsubmenus: {}", stats.submenus);
+        println!("\tmax nesting: {}", stats.max_nesting);
+        println!("\tentries with aliases: {}", stats.with_aliases);
+
+        for path in &stats.empty_lists {
+            println!("\terror: empty list at {path:?}");
+        }
+        for path in &stats.empty_names {
+            println!("\terror: empty name at {path:?}");
+        }
+        for path in &stats.empty_texts {
+            println!("\terror: empty text at {path:?}");
+        }
+        for (glyph, names) in &stats.glyphs {
+            if names.len() > 1 {
+                println!("\twarning: glyph {glyph:?} is used by {names:?}");
+            }
+        }
+
+        problems += stats.empty_lists.len() + stats.empty_names.len() + stats.empty_texts.len();
+    }
+
+    anyhow::ensure!(problems == 0, "found {problems} structural problems");
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Stats {
+    entries: usize,
+    submenus: usize,
+    max_nesting: usize,
+    with_aliases: usize,
+    /// The paths of every empty list.
+    empty_lists: Vec<String>,
+    /// The paths of every item with an empty name.
+    empty_names: Vec<String>,
+    /// The paths of every item that copies empty text.
+    empty_texts: Vec<String>,
+    /// Every glyph mapped to the names of the entries that copy it.
+    glyphs: BTreeMap<String, Vec<String>>,
+}
+
+impl Stats {
+    fn visit(&mut self, items: &Items, path: &mut Vec<String>, nesting: usize) {
+        self.max_nesting = self.max_nesting.max(nesting);
+
+        if items.direct.is_empty() && items.extends.is_empty() {
+            self.empty_lists.push(path.join(" / "));
+        }
+
+        for item in &items.direct {
+            path.push(item.name.clone());
+
+            if item.name.is_empty() {
+                self.empty_names.push(path.join(" / "));
+            }
+
+            match &item.content {
+                Content::Text(glyph) => {
+                    self.entries += 1;
+                    if glyph.is_empty() {
+                        self.empty_texts.push(path.join(" / "));
+                    }
+                    // Aliases are appended to the name in parentheses by the generators.
+                    if item.name.ends_with(')') && item.name.contains(" (") {
+                        self.with_aliases += 1;
+                    }
+                    self.glyphs
+                        .entry(glyph.clone())
+                        .or_default()
+                        .push(item.name.clone());
+                }
+                Content::Items(nested) => {
+                    self.submenus += 1;
+                    self.visit(nested, path, nesting + 1);
+                }
+                Content::Separator => {}
+            }
+
+            path.pop();
+        }
+    }
+}
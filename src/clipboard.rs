@@ -0,0 +1,424 @@
+//! Copying text to the clipboard.
+
+use ::{
+    serde::Deserialize,
+    std::{
+        io::{self, Read, Write},
+        process,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// An X selection that text can be copied into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Selection {
+    /// The selection used by explicit copy and paste commands, such as Ctrl+C and Ctrl+V.
+    Clipboard,
+    /// The selection pasted by middle-clicking.
+    Primary,
+    /// The rarely-used secondary selection.
+    Secondary,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::Clipboard
+    }
+}
+
+impl Selection {
+    /// The selection of the name `xclip` knows it by, such as `clipboard`.
+    pub fn from_xclip_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "clipboard" => Self::Clipboard,
+            "primary" => Self::Primary,
+            "secondary" => Self::Secondary,
+            _ => return None,
+        })
+    }
+
+    fn xclip_name(self) -> &'static str {
+        match self {
+            Self::Clipboard => "clipboard",
+            Self::Primary => "primary",
+            Self::Secondary => "secondary",
+        }
+    }
+}
+
+/// An error copying text.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The clipboard tool could not be run, usually because it is not installed.
+    #[error("failed to spawn {0}: {1}")]
+    Spawn(String, #[source] io::Error),
+    /// The clipboard tool exited before accepting all the text.
+    #[error("failed to write to {0}: {1}")]
+    Write(String, #[source] io::Error),
+    /// The clipboard tool did not accept the text in time, and was killed.
+    #[error("{0} did not accept the text within {1:?} and was killed")]
+    Timeout(String, Duration),
+    /// The clipboard command has no program to run.
+    #[error("the clipboard command is empty")]
+    EmptyCommand,
+    /// Copying into more than one selection failed.
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Several(Vec<Error>),
+}
+
+impl Error {
+    /// Whether copying failed only because the clipboard tool is not installed.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::Spawn(_, e) => e.kind() == io::ErrorKind::NotFound,
+            Self::Several(errors) => errors.iter().all(Self::is_unavailable),
+            Self::Write(..) | Self::Timeout(..) | Self::EmptyCommand => false,
+        }
+    }
+}
+
+/// The placeholder in the arguments of a clipboard command that is replaced by the text.
+pub const PLACEHOLDER: &str = "{}";
+
+/// Configuration of how text is copied.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    /// The X selections to copy into when using `xclip`, which is run once for each.
+    pub selections: Vec<Selection>,
+    /// How long to wait for the clipboard tool to accept the text before killing it.
+    pub timeout: Duration,
+    /// The program and arguments to copy with instead of `xclip`.
+    ///
+    /// If an argument contains [`PLACEHOLDER`], the text is passed in its place;
+    /// otherwise it is written to the program's standard input.
+    pub command: Option<Vec<String>>,
+    /// The program run as `xclip`, found through `PATH` unless it is a path.
+    pub xclip: String,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            selections: vec![Selection::default()],
+            timeout: Duration::from_secs(3),
+            command: None,
+            xclip: "xclip".to_owned(),
+        }
+    }
+}
+
+impl Clipboard {
+    /// Copy `text` using the configured command, or `xclip` if there isn't one.
+    ///
+    /// Every selection is copied into even if copying into one of them fails.
+    pub fn copy(&self, text: &str) -> Result<(), Error> {
+        if let Some(command) = &self.command {
+            let (program, args) = command.split_first().ok_or(Error::EmptyCommand)?;
+            return self.run(program, args, text);
+        }
+
+        let mut errors = Vec::new();
+        for selection in &self.selections {
+            let args = [
+                "-selection".to_owned(),
+                selection.xclip_name().to_owned(),
+                "-quiet".to_owned(),
+            ];
+            if let Err(e) = self.run(&*self.xclip, &args, text) {
+                errors.push(e);
+            }
+        }
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.pop().unwrap()),
+            _ => Err(Error::Several(errors)),
+        }
+    }
+
+    /// Whether an X clipboard manager is running, judged by whether anything owns the
+    /// `CLIPBOARD_MANAGER` selection, or `None` if that can't be told,
+    /// such as when `xclip` isn't installed or doesn't answer in time.
+    ///
+    /// `xclip` keeps running after Rofi exits to serve the text it copied,
+    /// but once it exits for any other reason, the text is lost unless a clipboard manager,
+    /// such as `clipit`, `parcellite` or `xfce4-clipman`, has taken it over.
+    ///
+    /// This blocks for as long as `xclip` takes to answer, up to the timeout.
+    /// Returns `None` if `xclip` could not ask, such as when it cannot open the display.
+    pub fn manager_running(&self) -> Option<bool> {
+        let mut child = process::Command::new(&*self.xclip)
+            .args([
+                "-selection",
+                "CLIPBOARD_MANAGER",
+                "-target",
+                "TARGETS",
+                "-out",
+            ])
+            .current_dir("/")
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()
+            .ok()?;
+
+        // `Child` has no way to time out a wait, so it is polled instead.
+        // The answer is a short list of targets, so it never fills the pipe.
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().ok()? {
+                break status;
+            }
+            if start.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let mut stdout = Vec::new();
+        child.stdout.take()?.read_to_end(&mut stdout).ok()?;
+        let mut stderr = String::new();
+        child.stderr.take()?.read_to_string(&mut stderr).ok()?;
+
+        // Without a manager, `xclip` reports that the target is not available.
+        // Anything else it reports means it never got to ask.
+        if stderr.contains("not available") {
+            Some(false)
+        } else if status.success() && stderr.trim().is_empty() {
+            Some(!stdout.is_empty())
+        } else {
+            None
+        }
+    }
+
+    /// Run a clipboard tool to copy `text`.
+    fn run(&self, program: &str, args: &[String], text: &str) -> Result<(), Error> {
+        let mut builder = process::Command::new(program);
+        builder
+            .current_dir("/")
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null());
+
+        if args.iter().any(|arg| arg.contains(PLACEHOLDER)) {
+            let mut child = builder
+                .args(args.iter().map(|arg| arg.replace(PLACEHOLDER, text)))
+                .stdin(process::Stdio::null())
+                .spawn()
+                .map_err(|e| Error::Spawn(program.to_owned(), e))?;
+            // The text has been passed on already, so there is nothing to wait for;
+            // the child is reaped once it exits without holding up Rofi.
+            thread::spawn(move || child.wait());
+            return Ok(());
+        }
+
+        let mut child = builder
+            .args(args)
+            .stdin(process::Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Spawn(program.to_owned(), e))?;
+
+        // `Child` has no way to time out a write, so it is done on another thread.
+        let mut stdin = child.stdin.take().unwrap();
+        let text = text.to_owned();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // `stdin` is dropped afterward, which tells the tool that the text is complete.
+            let _ = sender.send(stdin.write_all(text.as_bytes()));
+        });
+
+        match receiver.recv_timeout(self.timeout) {
+            Ok(res) => res.map_err(|e| Error::Write(program.to_owned(), e)),
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(Error::Timeout(program.to_owned(), self.timeout))
+            }
+        }
+    }
+}
+
+/// Copy `text` into the given selection using `xclip`.
+pub fn copy(text: &str, selection: Selection) -> Result<(), Error> {
+    Clipboard {
+        selections: vec![selection],
+        ..Clipboard::default()
+    }
+    .copy(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            env, fs,
+            os::unix::fs::PermissionsExt as _,
+            path::{Path, PathBuf},
+        },
+    };
+
+    /// A clipboard that copies with a shell script, given the path of a file it can write to.
+    fn script_clipboard(name: &str, script: &str) -> (Clipboard, PathBuf) {
+        let path = env::temp_dir().join(format!("rofi-unicode-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_file(&*path);
+        let command = ["sh", "-c", script, "sh", path.to_str().unwrap(), "{}"];
+        let clipboard = Clipboard {
+            selections: Vec::new(),
+            timeout: Duration::from_secs(5),
+            command: Some(command.iter().map(|&arg| arg.to_owned()).collect()),
+            ..Clipboard::default()
+        };
+        (clipboard, path)
+    }
+
+    /// Write a shell script that stands in for `xclip`, returning its path.
+    fn fake_xclip(dir: &Path, script: &str) -> String {
+        let xclip = dir.join("xclip");
+        fs::write(&*xclip, format!("#!/bin/sh\n{script}")).unwrap();
+        let mut permissions = fs::metadata(&*xclip).unwrap().permissions();
+        permissions.set_mode(0o755);
+        fs::set_permissions(&*xclip, permissions).unwrap();
+        xclip.to_str().unwrap().to_owned()
+    }
+
+    /// Wait for a clipboard tool to write a file, as it may still be running after copying.
+    fn wait_for_file(path: &Path) -> String {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Ok(contents) = fs::read_to_string(path) {
+                return contents;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("{} was never written", path.display());
+    }
+
+    #[test]
+    fn hung_tool_is_killed() {
+        let clipboard = Clipboard {
+            selections: Vec::new(),
+            timeout: Duration::from_millis(100),
+            command: Some(vec!["sleep".to_owned(), "10".to_owned()]),
+            ..Clipboard::default()
+        };
+        // More than fits in a pipe, so writing it blocks until `sleep` reads it, which is never.
+        let text = "x".repeat(1 << 20);
+
+        let start = Instant::now();
+        let res = clipboard.copy(&*text);
+        assert!(matches!(res, Err(Error::Timeout(..))), "{res:?}");
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn text_on_stdin() {
+        // Written to a temporary file and then renamed, so it is never read half-written
+        let script = r#"cat > "$1.tmp" && mv "$1.tmp" "$1""#;
+        let (mut clipboard, path) = script_clipboard("text-on-stdin", script);
+        // Without the placeholder
+        clipboard.command.as_mut().unwrap().pop();
+        clipboard.copy("❤ $HOME '{}'").unwrap();
+        assert_eq!(wait_for_file(&*path), "❤ $HOME '{}'");
+    }
+
+    #[test]
+    fn text_as_argument() {
+        let script = r#"printf %s "$2" > "$1.tmp" && mv "$1.tmp" "$1""#;
+        let (clipboard, path) = script_clipboard("text-as-argument", script);
+        clipboard.copy("❤ $HOME '{}'").unwrap();
+        assert_eq!(wait_for_file(&*path), "❤ $HOME '{}'");
+    }
+
+    #[test]
+    fn xclip_for_each_selection() {
+        let dir = env::temp_dir().join(format!(
+            "rofi-unicode-test-{}-xclip-for-each-selection",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&*dir);
+        fs::create_dir_all(&*dir).unwrap();
+        let log = dir.join("log");
+
+        // A stand-in for `xclip` that logs the selection it is asked to copy into
+        let script = format!("cat > /dev/null\necho \"$2\" >> '{}'\n", log.display());
+        let clipboard = Clipboard {
+            selections: vec![Selection::Clipboard, Selection::Primary],
+            xclip: fake_xclip(&*dir, &*script),
+            ..Clipboard::default()
+        };
+        clipboard.copy("❤").unwrap();
+
+        // `xclip` may not have finished once copying returns.
+        let start = Instant::now();
+        let mut selections = Vec::new();
+        while start.elapsed() < Duration::from_secs(5) {
+            let contents = fs::read_to_string(&*log).unwrap_or_default();
+            selections = contents.lines().map(str::to_owned).collect::<Vec<_>>();
+            if selections.len() >= 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        selections.sort();
+        assert_eq!(selections, ["clipboard", "primary"]);
+    }
+
+    #[test]
+    fn unavailable() {
+        let clipboard = Clipboard {
+            command: Some(vec!["rofi-unicode-test-nonexistent-program".to_owned()]),
+            ..Clipboard::default()
+        };
+        let e = clipboard.copy("❤").unwrap_err();
+        assert!(e.is_unavailable(), "{e:?}");
+    }
+
+    #[test]
+    fn empty_command() {
+        let clipboard = Clipboard {
+            command: Some(Vec::new()),
+            ..Clipboard::default()
+        };
+        let e = clipboard.copy("❤").unwrap_err();
+        assert!(matches!(e, Error::EmptyCommand), "{e:?}");
+        assert!(!e.is_unavailable());
+    }
+
+    #[test]
+    fn manager_running() {
+        let dir = env::temp_dir().join(format!(
+            "rofi-unicode-test-{}-manager-running",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&*dir);
+        fs::create_dir_all(&*dir).unwrap();
+
+        let answers = [
+            ("echo TARGETS\necho UTF8_STRING\n", Some(true)),
+            (
+                "echo 'Error: target TARGETS not available' >&2\nexit 1\n",
+                Some(false),
+            ),
+            (
+                "echo \"Error: Can't open display: (null)\" >&2\nexit 1\n",
+                None,
+            ),
+            ("exit 1\n", None),
+        ];
+        for (script, running) in answers {
+            let clipboard = Clipboard {
+                xclip: fake_xclip(&*dir, script),
+                ..Clipboard::default()
+            };
+            assert_eq!(clipboard.manager_running(), running, "{script}");
+        }
+
+        let clipboard = Clipboard {
+            xclip: "rofi-unicode-test-nonexistent-program".to_owned(),
+            ..Clipboard::default()
+        };
+        assert_eq!(clipboard.manager_running(), None);
+    }
+}
@@ -1,101 +1,1309 @@
-use ::{
-    anyhow::{anyhow, Context as _},
-    serde::{
-        de::{self, Deserializer},
-        Deserialize,
-    },
-    std::{
-        env,
-        ffi::OsString,
-        fmt, fs, io,
-        os::unix::ffi::{OsStrExt as _, OsStringExt as _},
-        path::{Path, PathBuf},
+//! Loading of the user's configuration, with all includes resolved.
+
+use {
+    crate::clipboard::{self, Selection},
+    ::{
+        anyhow::{anyhow, Context as _},
+        pango::IsAttribute as _,
+        serde::{
+            de::{self, Deserializer},
+            Deserialize,
+        },
+        std::{
+            collections::{HashMap, HashSet},
+            env,
+            ffi::OsString,
+            fmt, fs, io,
+            os::unix::ffi::{OsStrExt as _, OsStringExt as _},
+            path::{Path, PathBuf},
+            process::{Command, Stdio},
+            time::{Duration, Instant},
+        },
     },
 };
 
-pub(crate) struct Config {
-    pub(crate) root: Vec<Item>,
+/// A fully resolved configuration.
+pub struct Config {
+    /// The root list.
+    pub root: List,
+    /// The maximum number of nested submenus and includes.
+    pub max_depth: usize,
+    /// Whether to show an entry at the top of each list that copies a random character from it.
+    pub show_random: bool,
+    /// Whether to append a newline to text when copying it.
+    pub append_newline: bool,
+    /// The normalization form to convert text to when copying it.
+    pub copy_normalization: Normalization,
+    /// Whether to trim ASCII whitespace from around text when copying it.
+    pub trim_copy: bool,
+    /// Whether to copy the input itself when it is accepted as custom input.
+    pub allow_custom_input: bool,
+    /// Whether invisible characters must be selected twice to be copied.
+    pub confirm_invisible: bool,
+    /// How long to wait for the clipboard tool to accept text before giving up.
+    pub clipboard_timeout: Duration,
+    /// The X selections to copy text into.
+    pub clipboard_selections: Vec<Selection>,
+    /// The program and arguments to copy text with instead of `xclip`, if any.
+    pub clipboard_command: Option<Vec<String>>,
+    /// Whether to print text to standard output when there is no clipboard tool to copy it with.
+    pub stdout_fallback: bool,
+    /// What Rofi does after text is copied.
+    pub after_copy: AfterCopy,
+    /// What cancelling does.
+    pub cancel_behavior: CancelBehavior,
+    /// The most items a search shows, if there is a limit.
+    pub max_results: Option<usize>,
+    /// Map from the number of each custom command Rofi sends, counting from zero,
+    /// to the action it is bound to.
+    pub key_actions: HashMap<u8, KeyAction>,
+    /// The URL of the reference page of a character, with `{codepoint}` in place of its code point.
+    pub reference_url: String,
+    /// Likely mistakes in the configuration, such as items copying empty text,
+    /// which are reported as warnings unless `strict` makes them errors.
+    pub warnings: Vec<String>,
 }
 
-pub(crate) struct Item {
-    pub(crate) name: String,
-    pub(crate) name_attributes: Vec<pango::Attribute>,
-    pub(crate) name_markup: String,
-    pub(crate) content: Content,
+/// An item of a list.
+pub struct Item {
+    /// The name of the item with all markup removed.
+    pub name: String,
+    /// The attributes described by the markup in the item's name,
+    /// indexing into `name`.
+    pub name_attributes: Vec<pango::Attribute>,
+    /// The name of the item as written in the configuration, as Pango markup.
+    pub name_markup: String,
+    /// Extra text that the item can be found by when searching, but which is not displayed.
+    pub search_aliases: Vec<String>,
+    /// How far ahead of the other items of its list the item is shown when searching.
+    pub weight: i32,
+    pub content: Content,
 }
 
-pub(crate) enum Content {
+pub enum Content {
+    /// Text to copy when the item is selected.
     Text(String),
-    Items(Vec<Item>),
+    /// A submenu of further items.
+    Items(List),
+    /// A row that separates the items around it, and cannot be selected or searched for.
+    Separator,
+}
+
+/// A list of items, shown either as the root list or as a submenu.
+#[derive(Default)]
+pub struct List {
+    pub items: Vec<Item>,
+    /// How searches are matched against the items of the list, if not the default.
+    pub match_mode: Option<MatchMode>,
+    /// The template that text copied from the list is put through, if any.
+    pub copy_template: Option<CopyTemplate>,
+    /// How the items of the list are displayed, if not the default.
+    pub display: Option<DisplayMode>,
+}
+
+/// How searches are matched against the items of a list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MatchMode {
+    /// Items match if their name starts with the input, ignoring case.
+    Prefix,
+    /// Items match according to Rofi's own matching,
+    /// which follows its `-matching` setting (`normal`, `regex`, `glob`, `fuzzy` or `prefix`).
+    /// `max_results` does not limit lists matched this way.
+    /// This is the default.
+    Fuzzy,
+    /// Items match if their name or search aliases contain the input, ignoring case.
+    Substring,
+}
+
+/// How the items of a list are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DisplayMode {
+    /// Each item is shown as its name. This is the default.
+    Rows,
+    /// Text items are shown as only the text they copy, without their name,
+    /// which suits a grid layout of Rofi's.
+    /// Items are still searched for by their name and search aliases.
+    Glyphs,
+}
+
+/// A template that copied text is put through, such as `&#x{:x};`.
+///
+/// It contains exactly one placeholder, which is one of:
+/// - `{}`, replaced by the text;
+/// - `{:x}` or `{:X}`, replaced by the code point of each character of the text in hexadecimal,
+///     with the template repeated for each character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyTemplate {
+    before: String,
+    placeholder: Placeholder,
+    after: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Text,
+    LowerHex,
+    UpperHex,
+}
+
+impl CopyTemplate {
+    /// Put text through the template.
+    pub fn apply(&self, text: &str) -> String {
+        let Self {
+            before,
+            placeholder,
+            after,
+        } = self;
+        match placeholder {
+            Placeholder::Text => format!("{before}{text}{after}"),
+            Placeholder::LowerHex => text
+                .chars()
+                .map(|c| format!("{before}{:x}{after}", u32::from(c)))
+                .collect(),
+            Placeholder::UpperHex => text
+                .chars()
+                .map(|c| format!("{before}{:X}{after}", u32::from(c)))
+                .collect(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CopyTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::parse(&*String::deserialize(deserializer)?)
+    }
+}
+
+impl CopyTemplate {
+    fn parse<E: de::Error>(template: &str) -> Result<Self, E> {
+        let placeholders = [
+            ("{}", Placeholder::Text),
+            ("{:x}", Placeholder::LowerHex),
+            ("{:X}", Placeholder::UpperHex),
+        ];
+        let mut found = placeholders
+            .iter()
+            .flat_map(|&(pattern, placeholder)| {
+                template
+                    .match_indices(pattern)
+                    .map(move |(i, _)| (i, pattern, placeholder))
+            })
+            .collect::<Vec<_>>();
+
+        match found.pop() {
+            Some((i, pattern, placeholder)) if found.is_empty() => Ok(Self {
+                before: template[..i].to_owned(),
+                placeholder,
+                after: template[i + pattern.len()..].to_owned(),
+            }),
+            _ => Err(de::Error::custom(format_args!(
+                "copy template {template:?} must contain exactly one `{{}}`, `{{:x}}` or `{{:X}}`"
+            ))),
+        }
+    }
 }
 
-pub(crate) fn read() -> anyhow::Result<Config> {
+/// A Unicode normalization form.
+///
+/// See <https://unicode.org/reports/tr15/>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Normalization {
+    /// Leave text as it is.
+    None,
+    /// Canonical composition.
+    Nfc,
+    /// Canonical decomposition.
+    Nfd,
+    /// Compatibility composition.
+    Nfkc,
+    /// Compatibility decomposition.
+    Nfkd,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// What Rofi does after text is copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AfterCopy {
+    /// Close Rofi. This is the default.
+    Exit,
+    /// Stay open on the current list.
+    Reload,
+    /// Switch to Rofi's next mode, as with `-modi`.
+    NextMode,
+}
+
+impl Default for AfterCopy {
+    fn default() -> Self {
+        Self::Exit
+    }
+}
+
+/// What cancelling, such as by pressing Escape, does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CancelBehavior {
+    /// Go back to the parent list, closing Rofi only from the root list. This is the default.
+    Up,
+    /// Close Rofi from any list.
+    Exit,
+}
+
+impl Default for CancelBehavior {
+    fn default() -> Self {
+        Self::Up
+    }
+}
+
+/// An action that can be bound to one of Rofi's custom keys, `kb-custom-1` to `kb-custom-19`.
+/// Which of these keys are pressed by which key combinations is set on Rofi's side,
+/// such as with `-kb-custom-1 Alt+1`, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum KeyAction {
+    /// Jump back to the root list. Bound to `kb-custom-1` by default.
+    Root,
+    /// Open the reference page of the selected character. Bound to `kb-custom-2` by default.
+    OpenReference,
+    /// Copy the name of the selected character instead of the character.
+    /// Bound to `kb-custom-3` by default.
+    CopyName,
+    /// Go back two levels at once, or to the root list if it is closer.
+    /// Bound to `kb-custom-4` by default.
+    /// Rofi's custom keys carry no argument, so there is no way to jump to an arbitrary ancestor.
+    UpTwo,
+    /// Copy the selected text as a shell `$'...'` string. Bound to `kb-custom-5` by default.
+    CopyShell,
+    /// Write where in the hierarchy Rofi is to the file at `$ROFI_UNICODE_STATE`.
+    /// Bound to `kb-custom-6` by default.
+    ///
+    /// Rofi gives modes no way to output anything on a custom key, so wrapper scripts instead
+    /// set `$ROFI_UNICODE_STATE` before running Rofi and read the file once it exits.
+    /// Its first line is the index of the active list, which is `0` for the root list,
+    /// and its second is the names of the submenus leading to it joined by ` / `,
+    /// which is empty for the root list.
+    WriteState,
+}
+
+impl KeyAction {
+    const ALL: [Self; 6] = [
+        Self::Root,
+        Self::OpenReference,
+        Self::CopyName,
+        Self::UpTwo,
+        Self::CopyShell,
+        Self::WriteState,
+    ];
+
+    /// The `N` of the `kb-custom-N` the action is bound to by default.
+    fn default_key(self) -> u8 {
+        match self {
+            Self::Root => 1,
+            Self::OpenReference => 2,
+            Self::CopyName => 3,
+            Self::UpTwo => 4,
+            Self::CopyShell => 5,
+            Self::WriteState => 6,
+        }
+    }
+}
+
+/// The number of custom keys Rofi has.
+const CUSTOM_KEYS: u8 = 19;
+
+/// An error reading the configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Neither `$XDG_CONFIG_HOME` nor `$HOME` is set.
+    #[error("$HOME environment variable is not set")]
+    NoHome,
+    /// `$ROFI_UNICODE_PROFILE` is not a valid profile name.
+    #[error("invalid profile name {0:?}")]
+    InvalidProfile(String),
+    /// The configuration file does not exist.
+    #[error("configuration file {} does not exist", .0.display())]
+    Missing(PathBuf),
+    /// The configuration file exists but could not be read.
+    #[error("failed to read file {}", .path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The configuration file is not valid.
+    #[error("failed to parse file {}", .path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// `clipboard_targets` is empty or names a selection that doesn't exist.
+    #[error("invalid clipboard targets {0:?}")]
+    InvalidClipboardTargets(Vec<String>),
+    /// `clipboard_command` is empty or contains more than one `{}`.
+    #[error("invalid clipboard command {0:?}")]
+    InvalidClipboardCommand(Vec<String>),
+    /// `reference_url` does not contain `{codepoint}`.
+    #[error("reference URL {0:?} does not contain {{codepoint}}")]
+    InvalidReferenceUrl(String),
+    /// An action in `keybindings` is bound to a custom key Rofi doesn't have.
+    #[error("kb-custom-{0} does not exist; Rofi only has kb-custom-1 to kb-custom-19")]
+    InvalidKeybinding(u8),
+    /// Two actions are bound to the same custom key,
+    /// possibly because only one of them was moved from its default key.
+    #[error("kb-custom-{0} is bound to both {1:?} and {2:?}")]
+    DuplicateKeybinding(u8, KeyAction, KeyAction),
+    /// Files included with `extends` do not exist,
+    /// each described by its path and the chain of files that included it.
+    #[error("could not resolve included files:\n{}", .0.join("\n"))]
+    MissingIncludes(Vec<String>),
+    /// A file included with `extends` exists but could not be read or is not valid.
+    #[error("failed to load {path} (included by {included_by})")]
+    ParseInclude {
+        /// The path of the file as written in `extends`.
+        path: String,
+        /// The chain of files that included it.
+        included_by: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Resolving the items of the configuration failed.
+    #[error(transparent)]
+    Resolve(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Read the configuration from `$XDG_CONFIG_HOME/rofi-unicode/config.ron`,
+/// resolving includes from it and from `$XDG_CONFIG_DIRS`.
+///
+/// If `$ROFI_UNICODE_PROFILE` is set, `config.<profile>.ron` is read instead.
+///
+/// With the `embedded-data` feature,
+/// the generated data compiled into the library is used for any of these files that are missing.
+pub fn read() -> Result<Config, Error> {
     let paths = Paths::from_env()?;
 
-    let config_ron_path = paths.config_home().join("config.ron");
-    let config_ron = fs::read_to_string(&*config_ron_path)
-        .with_context(|| format!("failed to read file {}", config_ron_path.display()))?;
-    let base = ron::from_str::<Base>(&config_ron)
-        .with_context(|| format!("failed to parse file {}", config_ron_path.display()))?;
+    let config_ron_name = match env::var_os("ROFI_UNICODE_PROFILE") {
+        Some(profile) => {
+            let profile = profile
+                .into_string()
+                .map_err(|profile| Error::InvalidProfile(profile.to_string_lossy().into()))?;
+            if profile.is_empty() || profile.contains('/') {
+                return Err(Error::InvalidProfile(profile));
+            }
+            format!("config.{profile}.ron")
+        }
+        None => "config.ron".to_owned(),
+    };
+    read_from(&paths, config_ron_name).map(|(config, _)| config)
+}
+
+/// Read the root list of the file of the same name as the user's configuration
+/// in the first of `$XDG_CONFIG_DIRS`, if there is one.
+fn read_system_root(
+    paths: &Paths,
+    config_ron_name: &str,
+) -> Result<Option<UnresolvedItems>, Error> {
+    let path = match paths.bases.get(1) {
+        Some(system_base) => system_base.join(config_ron_name),
+        None => return Ok(None),
+    };
+    let config_ron = match fs::read_to_string(&*path) {
+        Ok(config_ron) => config_ron,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(Error::Read { path, source }),
+    };
+    match from_ron::<Base>(&config_ron) {
+        Ok(base) => Ok(Some(base.root)),
+        Err(e) => Err(Error::Parse {
+            path,
+            source: e.into(),
+        }),
+    }
+}
+
+/// How long each step of reading the configuration took.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// Reading and parsing the RON of `config.ron`,
+    /// and of any included file that is included more than once.
+    pub parse: Duration,
+    /// Checking that every included file exists,
+    /// which includes reading and parsing each of them the first time it is included.
+    pub check_includes: Duration,
+    /// Parsing the markup of item names.
+    pub markup: Duration,
+    /// The whole of reading the configuration, including the above.
+    pub total: Duration,
+}
+
+/// Read the configuration from `config_ron_name` in the first of `dirs`,
+/// resolving includes from all of them in order,
+/// and measure how long each step takes.
+///
+/// Unlike [`read`], this ignores the environment, so that it can be benchmarked.
+pub fn read_timed(dirs: Vec<PathBuf>, config_ron_name: &str) -> Result<(Config, Timings), Error> {
+    read_from(&Paths::new(dirs), config_ron_name.to_owned())
+}
+
+/// The items of a single file of the configuration, as they are written.
+///
+/// Includes are not resolved and names are not parsed,
+/// but keys are read exactly as they are when the configuration is read,
+/// so that tools that inspect generated files agree with it.
+pub struct FileItems {
+    pub extends: Vec<String>,
+    pub items: Vec<FileItem>,
+}
 
-    let mut root = Vec::new();
-    resolve(base.root, &mut root, &paths)?;
+/// An item of a [`FileItems`].
+pub struct FileItem {
+    /// The name of the item, which is its key unless it is given by `name`.
+    pub name: String,
+    pub search_aliases: Vec<String>,
+    pub content: FileContent,
+}
 
-    Ok(Config { root })
+/// What a [`FileItem`] is.
+pub enum FileContent {
+    Text(String),
+    Items(FileItems),
+    Separator,
 }
 
-fn resolve(
-    unresolved: UnresolvedItems,
-    resolved: &mut Vec<Item>,
+/// Parse the items of a file that is included by the configuration, such as `codepoints.ron`.
+pub fn parse_file_items(ron: &str) -> anyhow::Result<FileItems> {
+    from_ron::<UnresolvedItems>(ron).map(file_items)
+}
+
+fn file_items(items: UnresolvedItems) -> FileItems {
+    let items_of_file = items
+        .direct
+        .into_iter()
+        .map(|item| match item.content {
+            UnresolvedContent::Text(text) => FileItem {
+                name: text.name.unwrap_or(item.name),
+                search_aliases: text.search_aliases,
+                content: FileContent::Text(text.text),
+            },
+            UnresolvedContent::Items(items) => FileItem {
+                name: item.name,
+                search_aliases: Vec::new(),
+                content: FileContent::Items(file_items(items)),
+            },
+            UnresolvedContent::Separator => FileItem {
+                name: item.name,
+                search_aliases: Vec::new(),
+                content: FileContent::Separator,
+            },
+        })
+        .collect();
+    FileItems {
+        extends: items.extends,
+        items: items_of_file,
+    }
+}
+
+fn read_from(paths: &Paths, config_ron_name: String) -> Result<(Config, Timings), Error> {
+    let start = Instant::now();
+
+    let config_ron_path = paths.config_home().join(&*config_ron_name);
+    let config_ron = match fs::read_to_string(&*config_ron_path) {
+        Ok(config_ron) => config_ron,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match embedded(&*config_ron_name) {
+            Some(config_ron) => config_ron.to_owned(),
+            None => return Err(Error::Missing(config_ron_path)),
+        },
+        Err(source) => {
+            return Err(Error::Read {
+                path: config_ron_path,
+                source,
+            })
+        }
+    };
+    parse_config(paths, config_ron_name, config_ron_path, &*config_ron, start)
+}
+
+/// Read the configuration from the contents of a `config.ron` file,
+/// with `start` being when reading the file began.
+fn parse_config(
     paths: &Paths,
-) -> anyhow::Result<()> {
-    resolved.reserve(unresolved.direct.len());
-    for unresolved_item in unresolved.direct {
-        let content = match unresolved_item.content {
-            UnresolvedContent::Text(text) => Content::Text(text),
-            UnresolvedContent::Items(inner_items) => {
-                let mut resolved_inner_items = Vec::new();
-                resolve(inner_items, &mut resolved_inner_items, paths)?;
-                Content::Items(resolved_inner_items)
-            }
+    config_ron_name: String,
+    config_ron_path: PathBuf,
+    config_ron: &str,
+    start: Instant,
+) -> Result<(Config, Timings), Error> {
+    let mut timings = Timings::default();
+
+    let mut base = match from_ron::<Base>(config_ron) {
+        Ok(base) => base,
+        Err(e) => {
+            return Err(Error::Parse {
+                path: config_ron_path,
+                source: e.into(),
+            })
+        }
+    };
+    if base.merge_system_config {
+        if let Some(system_root) = read_system_root(paths, &*config_ron_name)? {
+            base.root = system_root.merged_with(base.root);
+        }
+    }
+    timings.parse += start.elapsed();
+
+    if let Some(command) = &base.clipboard_command {
+        let placeholders = command
+            .iter()
+            .map(|arg| arg.matches(clipboard::PLACEHOLDER).count())
+            .sum::<usize>();
+        if command.is_empty() || placeholders > 1 {
+            return Err(Error::InvalidClipboardCommand(command.clone()));
+        }
+    }
+
+    let clipboard_selections = base
+        .clipboard_targets
+        .iter()
+        .map(|target| Selection::from_xclip_name(target))
+        .collect::<Option<Vec<_>>>()
+        .filter(|selections| !selections.is_empty())
+        .ok_or_else(|| Error::InvalidClipboardTargets(base.clipboard_targets.clone()))?;
+
+    if !base.reference_url.contains("{codepoint}") {
+        return Err(Error::InvalidReferenceUrl(base.reference_url));
+    }
+
+    // Keyed by the number of the custom command Rofi sends, which counts from zero.
+    let mut key_actions = HashMap::new();
+    for action in KeyAction::ALL {
+        let key = match base.keybindings.get(&action) {
+            Some(&key) => key,
+            None => action.default_key(),
         };
+        if !(1..=CUSTOM_KEYS).contains(&key) {
+            return Err(Error::InvalidKeybinding(key));
+        }
+        if let Some(&other) = key_actions.get(&(key - 1)) {
+            return Err(Error::DuplicateKeybinding(key, other, action));
+        }
+        key_actions.insert(key - 1, action);
+    }
 
-        let (name_attribute_list, name, _) = pango::parse_markup(&*unresolved_item.name, '\0')
-            .with_context(|| {
-                format!(
-                    "item name {:?} contains invalid markup",
-                    unresolved_item.name
-                )
-            })?;
-
-        resolved.push(Item {
-            name: name.into(),
-            name_attributes: name_attribute_list.attributes(),
-            name_markup: unresolved_item.name,
-            content,
-        });
+    let check_start = Instant::now();
+    let mut missing = Vec::new();
+    let mut includes = HashMap::new();
+    find_missing_includes(
+        paths,
+        &base.root,
+        &mut vec![config_ron_name.clone()],
+        base.max_depth,
+        &mut HashSet::new(),
+        &mut includes,
+        &mut missing,
+    )?;
+    if !missing.is_empty() {
+        return Err(Error::MissingIncludes(missing));
+    }
+    timings.check_includes = check_start.elapsed();
+
+    let mut resolver = Resolver {
+        paths,
+        include_chain: vec![config_ron_name],
+        item_path: Vec::new(),
+        depth: 0,
+        max_depth: base.max_depth,
+        strip_markup_from_text: base.strip_markup_from_text,
+        dim_codepoint: base.dim_codepoint,
+        max_name_len: base.max_name_len,
+        strict: base.strict,
+        warnings: Vec::new(),
+        allow_commands: base.allow_commands,
+        timings,
+        includes,
+        markup_cache: HashMap::new(),
+    };
+    let mut root = List::default();
+    resolver
+        .resolve(base.root, &mut root)
+        .map_err(|e| Error::Resolve(e.into()))?;
+    if base.max_results.is_some() && has_fuzzy_list(&root) {
+        let message = "`max_results` does not limit lists with a `match_mode` of `Fuzzy`, \
+            which is the default"
+            .to_owned();
+        resolver
+            .warn(message)
+            .map_err(|e| Error::Resolve(e.into()))?;
     }
 
-    for include_path in unresolved.extends {
-        let include_ron = paths.read_to_string(&*include_path)?;
-        let include = ron::from_str::<UnresolvedItems>(&*include_ron)
-            .with_context(|| format!("failed to deserialize included file {include_path}"))?;
+    let mut timings = resolver.timings;
+    timings.total = start.elapsed();
+
+    let config = Config {
+        root,
+        max_depth: base.max_depth,
+        show_random: base.show_random,
+        append_newline: base.append_newline,
+        copy_normalization: base.copy_normalization,
+        trim_copy: base.trim_copy,
+        allow_custom_input: base.allow_custom_input,
+        confirm_invisible: base.confirm_invisible,
+        clipboard_timeout: Duration::from_millis(base.clipboard_timeout_ms),
+        clipboard_selections,
+        clipboard_command: base.clipboard_command,
+        stdout_fallback: base.stdout_fallback,
+        after_copy: base.after_copy,
+        cancel_behavior: base.cancel_behavior,
+        max_results: base.max_results,
+        key_actions,
+        reference_url: base.reference_url,
+        warnings: resolver.warnings,
+    };
+    Ok((config, timings))
+}
+
+/// Whether a list or any of its submenus is matched by Rofi.
+fn has_fuzzy_list(list: &List) -> bool {
+    list.match_mode
+        .map_or(true, |mode| mode == MatchMode::Fuzzy)
+        || list.items.iter().any(|item| match &item.content {
+            Content::Items(list) => has_fuzzy_list(list),
+            Content::Text(_) | Content::Separator => false,
+        })
+}
 
-        resolve(include, resolved, paths)?;
+/// Read a configuration from the contents of a `config.ron` file
+/// without any other files to include, for testing.
+#[cfg(test)]
+pub(crate) fn from_str(config_ron: &str) -> Result<Config, Error> {
+    let paths = Paths { bases: Vec::new() };
+    let path = PathBuf::from("config.ron");
+    parse_config(
+        &paths,
+        "config.ron".to_owned(),
+        path,
+        config_ron,
+        Instant::now(),
+    )
+    .map(|(config, _)| config)
+}
+
+struct Resolver<'a> {
+    paths: &'a Paths,
+    /// The path of every file in the chain of `extends` that led to the current items,
+    /// starting with `config.ron`.
+    include_chain: Vec<String>,
+    /// The names of the submenus that led to the current items.
+    item_path: Vec<String>,
+    /// The number of submenus and includes that led to the current items.
+    depth: usize,
+    max_depth: usize,
+    strip_markup_from_text: bool,
+    dim_codepoint: bool,
+    max_name_len: Option<usize>,
+    strict: bool,
+    /// The likely mistakes found so far, if not `strict`.
+    warnings: Vec<String>,
+    allow_commands: bool,
+    timings: Timings,
+    /// The items of included files already parsed by [`find_missing_includes`],
+    /// keyed by their include path.
+    /// Each is taken out the first time it is included, so any later time parses it again.
+    includes: HashMap<String, UnresolvedItems>,
+    /// The plain text and attributes of every name with markup parsed so far.
+    markup_cache: HashMap<String, (String, Vec<pango::Attribute>)>,
+}
+
+impl Resolver<'_> {
+    /// Resolve a list of items, appending them to `resolved`.
+    ///
+    /// Options of the list that `resolved` doesn't yet have are taken from `unresolved`,
+    /// so that included files can provide options for the list including them.
+    fn resolve(&mut self, unresolved: UnresolvedItems, resolved: &mut List) -> anyhow::Result<()> {
+        if self.depth > self.max_depth {
+            anyhow::bail!(
+                "maximum nesting depth of {} exceeded at {:?} in {}",
+                self.max_depth,
+                self.item_path.join(" / "),
+                self.include_chain.join(" -> "),
+            );
+        }
+
+        resolved.match_mode = resolved.match_mode.or(unresolved.match_mode);
+        resolved.display = resolved.display.or(unresolved.display);
+        if resolved.copy_template.is_none() {
+            resolved.copy_template = unresolved.copy_template;
+        }
+
+        resolved.items.reserve(unresolved.direct.len());
+        for mut unresolved_item in unresolved.direct {
+            let mut search_aliases = Vec::new();
+            let mut literal = false;
+            let mut weight = 0;
+            let content = match unresolved_item.content {
+                UnresolvedContent::Text(text) => {
+                    search_aliases = text.search_aliases;
+                    literal = text.literal;
+                    weight = text.weight;
+                    if let Some(name) = text.name {
+                        unresolved_item.name = name;
+                    }
+                    let text = if self.strip_markup_from_text {
+                        let (_, plain, _) =
+                            pango::parse_markup(&*text.text, '\0').with_context(|| {
+                                format!(
+                                    "item text {:?} in {} contains invalid markup",
+                                    text.text,
+                                    self.current_file(),
+                                )
+                            })?;
+                        plain.into()
+                    } else {
+                        text.text
+                    };
+                    if text.is_empty() {
+                        let message = format!(
+                            "item {:?} in {} copies empty text",
+                            unresolved_item.name,
+                            self.include_chain.join(" -> "),
+                        );
+                        self.warn(message)?;
+                    }
+                    Content::Text(text)
+                }
+                UnresolvedContent::Separator => Content::Separator,
+                UnresolvedContent::Items(inner_items) => {
+                    let mut resolved_inner_items = List::default();
+                    self.item_path.push(unresolved_item.name.clone());
+                    self.nested(|this| this.resolve(inner_items, &mut resolved_inner_items))?;
+                    self.item_path.pop();
+                    Content::Items(resolved_inner_items)
+                }
+            };
+
+            let markup_start = Instant::now();
+            let (mut name, mut name_attributes, mut name_markup) = if literal {
+                let name_markup = pango::glib::markup_escape_text(&*unresolved_item.name);
+                (unresolved_item.name, Vec::new(), name_markup.into())
+            } else {
+                let (name, name_attributes) = self
+                    .parse_name_markup(&*unresolved_item.name)
+                    .with_context(|| {
+                        format!(
+                            "item name {:?} in {} contains invalid markup",
+                            unresolved_item.name,
+                            self.current_file(),
+                        )
+                    })?;
+                (name, name_attributes, unresolved_item.name)
+            };
+            self.timings.markup += markup_start.elapsed();
+
+            // Separators may well be meant to be blank.
+            if name.chars().all(char::is_whitespace) && !matches!(content, Content::Separator) {
+                let message = format!(
+                    "item name {name_markup:?} in {} displays as a blank row",
+                    self.current_file(),
+                );
+                self.warn(message)?;
+            }
+
+            if let Some(max_len) = self.max_name_len {
+                if let Some(truncated_markup) = truncate_markup(&*name_markup, max_len) {
+                    let (attribute_list, truncated, _) =
+                        pango::parse_markup(&*truncated_markup, '\0').with_context(|| {
+                            format!("failed to truncate item name {name_markup:?}")
+                        })?;
+                    // The full name can still be searched for.
+                    search_aliases.push(name);
+                    name = truncated.into();
+                    name_attributes = attribute_list.attributes();
+                    name_markup = truncated_markup;
+                }
+            }
+
+            if self.dim_codepoint {
+                if let Some(len) = codepoint_prefix_len(&*name) {
+                    let mut attribute = pango::AttrInt::new_foreground_alpha(u16::MAX / 2).upcast();
+                    attribute.set_start_index(0);
+                    attribute.set_end_index(len as u32);
+                    name_attributes.push(attribute);
+                }
+            }
+
+            resolved.items.push(Item {
+                name,
+                name_attributes,
+                name_markup,
+                search_aliases,
+                weight,
+                content,
+            });
+        }
+
+        for include_path in unresolved.extends {
+            let circular = self.include_chain.contains(&include_path);
+            self.include_chain.push(include_path);
+            if circular {
+                anyhow::bail!("circular include: {}", self.include_chain.join(" -> "));
+            }
+            let include_path = self.include_chain.last().unwrap();
+
+            let include = match self.includes.remove(include_path) {
+                Some(include) => include,
+                None => {
+                    let parse_start = Instant::now();
+                    let include = read_include(self.paths, include_path).with_context(|| {
+                        format!("while resolving {}", self.include_chain.join(" -> "))
+                    })?;
+                    self.timings.parse += parse_start.elapsed();
+                    include
+                }
+            };
+
+            self.nested(|this| this.resolve(include, resolved))?;
+            self.include_chain.pop();
+        }
+
+        if let Some(command) = unresolved.from_command {
+            self.include_chain.push(format!("`{}`", command.join(" ")));
+
+            let include = if self.allow_commands {
+                run_command(&*command)
+            } else {
+                Err(anyhow!(
+                    "`from_command` is disabled; set `allow_commands: true` to enable it"
+                ))
+            }
+            .with_context(|| format!("while resolving {}", self.include_chain.join(" -> ")))?;
+
+            self.nested(|this| this.resolve(include, resolved))?;
+            self.include_chain.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Parse the markup of an item's name into its plain text and attributes.
+    ///
+    /// Names without any tags or entities are their own plain text, so aren't parsed at all.
+    /// The rest are memoized, as the same items often appear in several generated files.
+    fn parse_name_markup(
+        &mut self,
+        markup: &str,
+    ) -> anyhow::Result<(String, Vec<pango::Attribute>)> {
+        if !markup.contains(['<', '&']) {
+            return Ok((markup.to_owned(), Vec::new()));
+        }
+        if let Some(parsed) = self.markup_cache.get(markup) {
+            return Ok(parsed.clone());
+        }
+        let (attribute_list, name, _) = pango::parse_markup(markup, '\0')?;
+        let parsed = (String::from(name), attribute_list.attributes());
+        self.markup_cache.insert(markup.to_owned(), parsed.clone());
+        Ok(parsed)
+    }
+
+    /// Report a likely mistake in the configuration, which is an error if `strict`.
+    fn warn(&mut self, message: String) -> anyhow::Result<()> {
+        if self.strict {
+            anyhow::bail!(message);
+        }
+        self.warnings.push(message);
+        Ok(())
+    }
+
+    /// The path of the file the current items are in.
+    fn current_file(&self) -> &str {
+        self.include_chain.last().unwrap()
+    }
+
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+}
+
+/// Walk the whole graph of `extends` up front,
+/// recording every included file that doesn't exist along with the chain that led to it,
+/// so that they can all be reported at once instead of one at a time.
+///
+/// Each include path is walked only once, however many times it is included,
+/// and the items of every included file are kept in `includes`
+/// so that [`Resolver::resolve`] doesn't parse them again.
+/// An included file that can't be parsed is reported straight away.
+fn find_missing_includes(
+    paths: &Paths,
+    items: &UnresolvedItems,
+    include_chain: &mut Vec<String>,
+    remaining_depth: usize,
+    visited: &mut HashSet<String>,
+    includes: &mut HashMap<String, UnresolvedItems>,
+    missing: &mut Vec<String>,
+) -> Result<(), Error> {
+    // Exceeding the maximum depth is reported by `resolve` instead.
+    let depth = match remaining_depth.checked_sub(1) {
+        Some(depth) => depth,
+        None => return Ok(()),
+    };
+
+    for item in &items.direct {
+        if let UnresolvedContent::Items(inner_items) = &item.content {
+            find_missing_includes(
+                paths,
+                inner_items,
+                include_chain,
+                depth,
+                visited,
+                includes,
+                missing,
+            )?;
+        }
+    }
+
+    for include_path in &items.extends {
+        let file_path = include_path.split('#').next().unwrap();
+        if !paths.exists(file_path) {
+            missing.push(format!(
+                "{file_path} (included by {})",
+                include_chain.join(" -> ")
+            ));
+            continue;
+        }
+
+        if !visited.insert(include_path.clone()) {
+            continue;
+        }
+        let include = read_include(paths, include_path).map_err(|e| Error::ParseInclude {
+            path: include_path.clone(),
+            included_by: include_chain.join(" -> "),
+            source: e.into(),
+        })?;
+        include_chain.push(include_path.clone());
+        find_missing_includes(
+            paths,
+            &include,
+            include_chain,
+            depth,
+            visited,
+            includes,
+            missing,
+        )?;
+        include_chain.pop();
+        includes.insert(include_path.clone(), include);
     }
 
     Ok(())
 }
 
+/// Read and parse an included file, given its path as written in `extends`.
+///
+/// `file.ron#name` includes only the submenu called `name` in `file.ron`.
+fn read_include(paths: &Paths, include_path: &str) -> anyhow::Result<UnresolvedItems> {
+    let (file_path, section) = match include_path.split_once('#') {
+        Some((file_path, section)) => (file_path, Some(section)),
+        None => (include_path, None),
+    };
+
+    let include_ron = paths.read_to_string(file_path)?;
+    let include = from_ron::<UnresolvedItems>(&*include_ron)
+        .map_err(|e| match top_level_shape(&*include_ron) {
+            Some(shape) if shape != '{' => e.context(
+                "the file is not a map of items; if it was generated, \
+                it is from an incompatible version, \
+                so regenerate it with `cargo dev generate`",
+            ),
+            _ => e,
+        })
+        .with_context(|| format!("failed to deserialize included file {file_path}"))?;
+
+    match section {
+        Some(section) => select_section(include, section),
+        None => Ok(include),
+    }
+}
+
+/// Select the items of the submenu with the given name from a list.
+fn select_section(items: UnresolvedItems, section: &str) -> anyhow::Result<UnresolvedItems> {
+    let item = items
+        .direct
+        .into_iter()
+        .find(|item| item.name == section)
+        .with_context(|| format!("no item named {section:?}"))?;
+    match item.content {
+        UnresolvedContent::Items(items) => Ok(items),
+        UnresolvedContent::Text(_) | UnresolvedContent::Separator => {
+            anyhow::bail!("item {section:?} is not a submenu")
+        }
+    }
+}
+
+/// Run a command from the configuration and parse its output as a list of items.
+///
+/// The command is run with only `$PATH` and `$HOME` from the environment,
+/// so that it does not depend on how Rofi happened to be started.
+fn run_command(command: &[String]) -> anyhow::Result<UnresolvedItems> {
+    let (program, args) = command.split_first().unwrap();
+    let mut builder = Command::new(program);
+    builder
+        .args(args)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for var in ["PATH", "HOME"] {
+        if let Some(value) = env::var_os(var) {
+            builder.env(var, value);
+        }
+    }
+
+    let output = builder.output().context("failed to run command")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "command failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&*output.stderr).trim_end(),
+        );
+    }
+
+    let stdout = String::from_utf8(output.stdout).context("command output is not UTF-8")?;
+    from_ron::<UnresolvedItems>(&*stdout).context("failed to deserialize command output")
+}
+
+/// Shorten Pango markup to at most `max_len` characters of text followed by an ellipsis,
+/// closing any tags left open, or return `None` if it is short enough already.
+///
+/// Tags are never split, and each entity counts as a single character.
+fn truncate_markup(markup: &str, max_len: usize) -> Option<String> {
+    let mut truncated = String::new();
+    let mut open_tags = Vec::new();
+    let mut len = 0;
+    let mut rest = markup;
+
+    while let Some(c) = rest.chars().next() {
+        let token_len = match c {
+            '<' => rest.find('>').map_or(rest.len(), |i| i + 1),
+            '&' => rest.find(';').map_or(1, |i| i + 1),
+            _ => c.len_utf8(),
+        };
+        let (token, after) = rest.split_at(token_len);
+        rest = after;
+
+        if c == '<' {
+            let tag = token.trim_start_matches('<').trim_end_matches('>');
+            if tag.starts_with('/') {
+                open_tags.pop();
+            } else if !tag.ends_with('/') {
+                open_tags.push(tag.split_whitespace().next().unwrap_or_default());
+            }
+        } else if len == max_len {
+            truncated.push('…');
+            for tag in open_tags.into_iter().rev() {
+                truncated.push_str("</");
+                truncated.push_str(tag);
+                truncated.push('>');
+            }
+            return Some(truncated);
+        } else {
+            len += 1;
+        }
+        truncated.push_str(token);
+    }
+
+    None
+}
+
+/// Get the length of the `U+XXXX` at the start of a name followed by a tab,
+/// as emitted by the generator, if there is one.
+fn codepoint_prefix_len(name: &str) -> Option<usize> {
+    let hex = name.strip_prefix("U+")?;
+    let digits = hex.bytes().take_while(u8::is_ascii_hexdigit).count();
+    if !(4..=6).contains(&digits) || hex.as_bytes().get(digits) != Some(&b'\t') {
+        return None;
+    }
+    Some("U+".len() + digits)
+}
+
+/// Find the first character of a RON document's value, skipping whitespace and comments,
+/// which is `{` for a map of items.
+fn top_level_shape(ron: &str) -> Option<char> {
+    let mut rest = ron;
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            return rest.chars().next();
+        }
+    }
+}
+
+/// Parse a RON document, used for both `config.ron` and every included file
+/// so that they accept exactly the same syntax.
+///
+/// `//` and `/* */` comments, blank lines and trailing commas are all part of core RON
+/// and need no extensions to be enabled.
+fn from_ron<'de, T: Deserialize<'de>>(s: &'de str) -> anyhow::Result<T> {
+    ron::Options::default().from_str(s).map_err(|e| {
+        anyhow::Error::new(e).context(
+            "invalid RON (comments, blank lines and trailing commas are allowed; \
+            see <https://github.com/ron-rs/ron>)",
+        )
+    })
+}
+
 #[derive(Deserialize)]
 #[serde(rename = "Config", deny_unknown_fields)]
 struct Base {
     root: UnresolvedItems,
+    /// The maximum number of nested submenus and includes.
+    #[serde(default = "default_max_depth")]
+    max_depth: usize,
+    /// Whether to show an entry at the top of each list that copies a random character from it.
+    #[serde(default)]
+    show_random: bool,
+    /// Whether to treat the text copied by items as Pango markup, and copy only its plain text.
+    #[serde(default)]
+    strip_markup_from_text: bool,
+    /// Whether to append a newline to text when copying it.
+    #[serde(default)]
+    append_newline: bool,
+    /// The normalization form to convert text to when copying it.
+    #[serde(default)]
+    copy_normalization: Normalization,
+    /// Whether to trim ASCII whitespace from around text when copying it,
+    /// unless the text is only whitespace.
+    #[serde(default)]
+    trim_copy: bool,
+    /// Whether to copy the input itself when it is accepted as custom input,
+    /// such as by pressing Enter when nothing matches.
+    /// The input can still be decoded into its characters with `kb-accept-custom-alt`.
+    #[serde(default)]
+    allow_custom_input: bool,
+    /// Whether items that copy only invisible characters, such as zero-width spaces
+    /// and bidirectional overrides, must be selected a second time to be copied,
+    /// so that they aren't pasted somewhere unknowingly.
+    #[serde(default)]
+    confirm_invisible: bool,
+    /// Whether to de-emphasize the `U+XXXX` at the start of generated names.
+    #[serde(default)]
+    dim_codepoint: bool,
+    /// The most characters of an item's name to display, written as `Some(n)`,
+    /// with any more replaced by an ellipsis.
+    /// Truncated items can still be found by their full name.
+    /// `None`, for no limit, is the default.
+    #[serde(default)]
+    max_name_len: Option<usize>,
+    /// How many milliseconds to wait for the clipboard tool to accept text before killing it.
+    #[serde(default = "default_clipboard_timeout_ms")]
+    clipboard_timeout_ms: u64,
+    /// The X selections to copy text into, each of `"clipboard"`, `"primary"` or `"secondary"`,
+    /// such as `["clipboard", "primary"]` to paste with both Ctrl+V and middle-click.
+    /// `["clipboard"]` is the default.
+    #[serde(default = "default_clipboard_targets")]
+    clipboard_targets: Vec<String>,
+    /// The program and arguments to copy text with instead of `xclip`,
+    /// written as `Some(["wl-copy"])`,
+    /// which ignores `clipboard_targets`.
+    /// The text is written to the program's standard input,
+    /// unless one argument contains `{}`, in which case the text is passed in its place.
+    #[serde(default)]
+    clipboard_command: Option<Vec<String>>,
+    /// Whether to print text to Rofi's standard output instead when the clipboard tool
+    /// is not installed, such as over SSH, so that a wrapper script can still capture it.
+    /// This is on by default.
+    #[serde(default = "default_stdout_fallback")]
+    stdout_fallback: bool,
+    /// What Rofi does after text is copied: `Exit`, `Reload` or `NextMode`.
+    #[serde(default)]
+    after_copy: AfterCopy,
+    /// What cancelling does: `Up` or `Exit`.
+    #[serde(default)]
+    cancel_behavior: CancelBehavior,
+    /// The most items a search shows, written as `Some(n)`, or `None` for no limit.
+    /// This is the default.
+    ///
+    /// Rofi asks whether each item matches independently, so the limit can only be applied
+    /// when rofi-unicode does the matching itself.
+    /// Lists with a `match_mode` of `Fuzzy`, the default, are therefore not limited,
+    /// and setting a limit while any list is matched that way is warned about.
+    #[serde(default)]
+    max_results: Option<usize>,
+    /// The custom keys actions are bound to, overriding their defaults,
+    /// written as a map from each action to the `N` of its `kb-custom-N`, such as `{CopyName: 7}`.
+    /// No two actions may share a key.
+    #[serde(default)]
+    keybindings: HashMap<KeyAction, u8>,
+    /// The URL of the page opened to learn about a character,
+    /// with `{codepoint}` in place of its code point written as `XXXX`.
+    #[serde(default = "default_reference_url")]
+    reference_url: String,
+    /// Whether to treat likely mistakes in the configuration, such as items copying empty text,
+    /// as errors rather than warnings.
+    #[serde(default)]
+    strict: bool,
+    /// Whether lists may use `from_command` to run a command and include its output.
+    /// This is off by default, as it allows configuration files to run arbitrary programs.
+    #[serde(default)]
+    allow_commands: bool,
+    /// Whether to start from the root list of the system-wide configuration,
+    /// the file of the same name in the first of `$XDG_CONFIG_DIRS`, if there is one.
+    /// Its `extends` and items come before those of this file,
+    /// and its list options apply where this file doesn't set them.
+    /// Its other options are ignored.
+    #[serde(default)]
+    merge_system_config: bool,
+}
+
+fn default_max_depth() -> usize {
+    64
+}
+
+fn default_clipboard_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_clipboard_targets() -> Vec<String> {
+    vec!["clipboard".to_owned()]
+}
+
+fn default_stdout_fallback() -> bool {
+    true
+}
+
+fn default_reference_url() -> String {
+    "https://util.unicode.org/UnicodeJsps/character.jsp?a={codepoint}".to_owned()
 }
 
 struct UnresolvedItems {
     extends: Vec<String>,
+    /// A command whose output is included, as if it were a file in `extends`.
+    from_command: Option<Vec<String>>,
     direct: Vec<UnresolvedItem>,
+    match_mode: Option<MatchMode>,
+    copy_template: Option<CopyTemplate>,
+    display: Option<DisplayMode>,
+}
+
+impl UnresolvedItems {
+    /// Append the includes and items of `later` to these,
+    /// with the options of `later` taking precedence.
+    fn merged_with(mut self, later: Self) -> Self {
+        self.extends.extend(later.extends);
+        self.direct.extend(later.direct);
+        Self {
+            extends: self.extends,
+            from_command: later.from_command.or(self.from_command),
+            direct: self.direct,
+            match_mode: later.match_mode.or(self.match_mode),
+            copy_template: later.copy_template.or(self.copy_template),
+            display: later.display.or(self.display),
+        }
+    }
 }
 
 struct UnresolvedItem {
@@ -103,12 +1311,200 @@ struct UnresolvedItem {
     content: UnresolvedContent,
 }
 
+enum UnresolvedContent {
+    Text(UnresolvedText),
+    Items(UnresolvedItems),
+    Separator,
+}
+
+impl UnresolvedContent {
+    /// A text item written as just its text.
+    fn from_text(text: String) -> Self {
+        Self::Text(UnresolvedText {
+            text,
+            search_aliases: Vec::new(),
+            literal: false,
+            weight: 0,
+            name: None,
+        })
+    }
+}
+
+struct UnresolvedText {
+    text: String,
+    search_aliases: Vec<String>,
+    /// Whether the item's name is plain text rather than Pango markup.
+    literal: bool,
+    /// Items with a higher weight are shown before those with a lower one when searching.
+    weight: i32,
+    /// The name to display instead of the item's key.
+    name: Option<String>,
+}
+
+/// A map in the configuration, which is either a list of items or,
+/// if it contains `text` with a string value and no other items, a single text item,
+/// or if it contains `separator: true`, a separator.
+///
+/// Only `extends`, `match_mode` and `display` are always reserved.
+/// The other options are read as items where they couldn't be options:
+/// `text` that isn't a string or is alongside other items,
+/// `name`, `search_aliases`, `literal` and `weight` outside a text item,
+/// `separator` that isn't a boolean, `from_command` that isn't a list of strings,
+/// and `copy_template` that isn't a valid template or is in a text item.
+/// A text item named `match_mode` or `display` is written under another key,
+/// with its name given by `name`.
+struct UnresolvedMap {
+    extends: Vec<String>,
+    from_command: Option<Vec<String>>,
+    direct: Vec<UnresolvedItem>,
+    text: Option<String>,
+    name: Option<String>,
+    search_aliases: Option<Vec<String>>,
+    literal: Option<bool>,
+    weight: Option<i32>,
+    separator: bool,
+    match_mode: Option<MatchMode>,
+    copy_template: Option<CopyTemplate>,
+    display: Option<DisplayMode>,
+}
+
+impl UnresolvedMap {
+    fn into_content<E: de::Error>(self) -> Result<UnresolvedContent, E> {
+        if self.separator {
+            if !self.extends.is_empty()
+                || self.from_command.is_some()
+                || !self.direct.is_empty()
+                || self.text.is_some()
+                || self.name.is_some()
+                || self.search_aliases.is_some()
+                || self.literal.is_some()
+                || self.weight.is_some()
+                || self.match_mode.is_some()
+                || self.copy_template.is_some()
+                || self.display.is_some()
+            {
+                return Err(E::custom("a separator cannot have any other keys"));
+            }
+            return Ok(UnresolvedContent::Separator);
+        }
+        match self.text {
+            Some(text) => {
+                if !self.extends.is_empty()
+                    || self.from_command.is_some()
+                    || !self.direct.is_empty()
+                {
+                    return Err(E::custom(
+                        "an item with `text` cannot also contain other items",
+                    ));
+                }
+                if self.match_mode.is_some() {
+                    return Err(E::custom("`match_mode` can only be used in a list"));
+                }
+                if self.copy_template.is_some() {
+                    return Err(E::custom("`copy_template` can only be used in a list"));
+                }
+                if self.display.is_some() {
+                    return Err(E::custom("`display` can only be used in a list"));
+                }
+                Ok(UnresolvedContent::Text(UnresolvedText {
+                    text,
+                    search_aliases: self.search_aliases.unwrap_or_default(),
+                    literal: self.literal.unwrap_or(false),
+                    weight: self.weight.unwrap_or(0),
+                    name: self.name,
+                }))
+            }
+            None => self.into_items().map(UnresolvedContent::Items),
+        }
+    }
+
+    fn into_items<E: de::Error>(mut self) -> Result<UnresolvedItems, E> {
+        // Where only a list can be, a lone `text` is an item of that name.
+        if let Some(text) = self.text.take() {
+            if self.name.is_some()
+                || self.search_aliases.is_some()
+                || self.literal.is_some()
+                || self.weight.is_some()
+            {
+                return Err(E::custom("`text` can only be used in an item"));
+            }
+            self.direct.push(UnresolvedItem {
+                name: "text".to_owned(),
+                content: UnresolvedContent::from_text(text),
+            });
+        }
+        // `name`, `search_aliases`, `literal` and `weight` are only options alongside `text`.
+        if self.separator {
+            return Err(E::custom("`separator` can only be used in an item"));
+        }
+        Ok(UnresolvedItems {
+            extends: self.extends,
+            from_command: self.from_command,
+            direct: self.direct,
+            match_mode: self.match_mode,
+            copy_template: self.copy_template,
+            display: self.display,
+        })
+    }
+}
+
 impl<'de> Deserialize<'de> for UnresolvedItems {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        UnresolvedMap::deserialize(deserializer)?.into_items()
+    }
+}
+
+impl<'de> Deserialize<'de> for UnresolvedMap {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         enum MapKey {
             Extends,
+            FromCommand,
+            Text,
+            Name,
+            SearchAliases,
+            Literal,
+            Weight,
+            Separator,
+            MatchMode,
+            CopyTemplate,
+            Display,
             Other(String),
         }
+        impl MapKey {
+            fn new(s: &str) -> Option<Self> {
+                Some(match s {
+                    "extends" => Self::Extends,
+                    "from_command" => Self::FromCommand,
+                    "text" => Self::Text,
+                    "name" => Self::Name,
+                    "search_aliases" => Self::SearchAliases,
+                    "literal" => Self::Literal,
+                    "weight" => Self::Weight,
+                    "separator" => Self::Separator,
+                    "match_mode" => Self::MatchMode,
+                    "copy_template" => Self::CopyTemplate,
+                    "display" => Self::Display,
+                    _ => return None,
+                })
+            }
+
+            fn as_str(&self) -> &str {
+                match self {
+                    Self::Extends => "extends",
+                    Self::FromCommand => "from_command",
+                    Self::Text => "text",
+                    Self::Name => "name",
+                    Self::SearchAliases => "search_aliases",
+                    Self::Literal => "literal",
+                    Self::Weight => "weight",
+                    Self::Separator => "separator",
+                    Self::MatchMode => "match_mode",
+                    Self::CopyTemplate => "copy_template",
+                    Self::Display => "display",
+                    Self::Other(name) => name,
+                }
+            }
+        }
         impl<'de> Deserialize<'de> for MapKey {
             fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
                 struct Visitor;
@@ -120,19 +1516,11 @@ impl<'de> Deserialize<'de> for UnresolvedItems {
                     }
 
                     fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
-                        Ok(if v == "extends" {
-                            MapKey::Extends
-                        } else {
-                            MapKey::Other(v.to_owned())
-                        })
+                        Ok(MapKey::new(v).unwrap_or_else(|| MapKey::Other(v.to_owned())))
                     }
 
                     fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
-                        Ok(if v == "extends" {
-                            MapKey::Extends
-                        } else {
-                            MapKey::Other(v)
-                        })
+                        Ok(MapKey::new(&*v).unwrap_or(MapKey::Other(v)))
                     }
                 }
                 deserializer.deserialize_str(Visitor)
@@ -141,7 +1529,7 @@ impl<'de> Deserialize<'de> for UnresolvedItems {
 
         struct Visitor;
         impl<'de> de::Visitor<'de> for Visitor {
-            type Value = UnresolvedItems;
+            type Value = UnresolvedMap;
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 f.write_str("a map of key names to values")
@@ -149,13 +1537,48 @@ impl<'de> Deserialize<'de> for UnresolvedItems {
 
             fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
                 let mut extends = Vec::new();
+                let mut from_command = None;
                 let mut direct = Vec::new();
+                let mut text = None;
+                let mut display_name = None;
+                let mut search_aliases = None;
+                let mut literal = None;
+                let mut weight = None;
+                let mut separator = None;
+                let mut match_mode = None;
+                let mut copy_template = None;
+                let mut display = None;
+                // Keys that are only options in some maps and otherwise name items,
+                // along with the index in `direct` they would have as items
+                let mut deferred = Vec::new();
 
                 while let Some(key) = map.next_key::<MapKey>()? {
                     match key {
                         MapKey::Extends => {
                             extends.extend(map.next_value::<Vec<String>>()?);
                         }
+                        MapKey::MatchMode => {
+                            if match_mode.is_some() {
+                                return Err(de::Error::duplicate_field("match_mode"));
+                            }
+                            match_mode = Some(map.next_value::<MatchMode>()?);
+                        }
+                        MapKey::Display => {
+                            if display.is_some() {
+                                return Err(de::Error::duplicate_field("display"));
+                            }
+                            display = Some(map.next_value::<DisplayMode>()?);
+                        }
+                        MapKey::Text
+                        | MapKey::FromCommand
+                        | MapKey::Name
+                        | MapKey::SearchAliases
+                        | MapKey::Literal
+                        | MapKey::Weight
+                        | MapKey::Separator
+                        | MapKey::CopyTemplate => {
+                            deferred.push((direct.len(), key, map.next_value::<KeyValue>()?));
+                        }
                         MapKey::Other(name) => {
                             direct.push(UnresolvedItem {
                                 name,
@@ -165,18 +1588,138 @@ impl<'de> Deserialize<'de> for UnresolvedItems {
                     }
                 }
 
-                Ok(UnresolvedItems { extends, direct })
+                // A string `text` makes the map a text item,
+                // unless something else in the map could only be an item.
+                let is_text_item = extends.is_empty()
+                    && direct.is_empty()
+                    && deferred.iter().any(|(_, key, value)| {
+                        matches!((key, value), (MapKey::Text, KeyValue::Str(_)))
+                    });
+
+                // So that existing lists with items of these names keep working,
+                // they are only options where the item couldn't be meant instead.
+                let mut inserted = 0;
+                for (index, key, value) in deferred {
+                    let is_option = match (&key, &value) {
+                        (
+                            MapKey::Name | MapKey::SearchAliases | MapKey::Literal | MapKey::Weight,
+                            _,
+                        ) => is_text_item,
+                        (MapKey::Text, KeyValue::Str(_)) => is_text_item,
+                        (MapKey::FromCommand, KeyValue::Strings(_)) => true,
+                        (MapKey::Separator, KeyValue::Bool(_)) => true,
+                        (MapKey::CopyTemplate, KeyValue::Str(template)) => {
+                            !is_text_item
+                                && CopyTemplate::parse::<de::value::Error>(template).is_ok()
+                        }
+                        _ => false,
+                    };
+                    if !is_option {
+                        let content = value.into_content().ok_or_else(|| -> A::Error {
+                            match key {
+                                MapKey::Name
+                                | MapKey::SearchAliases
+                                | MapKey::Literal
+                                | MapKey::Weight => de::Error::custom(format_args!(
+                                    "`{}` can only be used alongside `text`",
+                                    key.as_str()
+                                )),
+                                _ => de::Error::custom(format_args!(
+                                    "invalid value of `{}`",
+                                    key.as_str()
+                                )),
+                            }
+                        })?;
+                        let item = UnresolvedItem {
+                            name: key.as_str().to_owned(),
+                            content,
+                        };
+                        direct.insert(index + inserted, item);
+                        inserted += 1;
+                        continue;
+                    }
+
+                    let invalid = || -> A::Error {
+                        de::Error::custom(format_args!("invalid value of `{}`", key.as_str()))
+                    };
+                    match key {
+                        MapKey::Text => {
+                            if text.is_some() {
+                                return Err(de::Error::duplicate_field("text"));
+                            }
+                            text = Some(value.into_string().ok_or_else(invalid)?);
+                        }
+                        MapKey::FromCommand => {
+                            if from_command.is_some() {
+                                return Err(de::Error::duplicate_field("from_command"));
+                            }
+                            let command = value.into_strings().ok_or_else(invalid)?;
+                            if command.is_empty() {
+                                return Err(de::Error::invalid_length(0, &"a program to run"));
+                            }
+                            from_command = Some(command);
+                        }
+                        MapKey::Name => {
+                            if display_name.is_some() {
+                                return Err(de::Error::duplicate_field("name"));
+                            }
+                            display_name = Some(value.into_string().ok_or_else(invalid)?);
+                        }
+                        MapKey::SearchAliases => {
+                            if search_aliases.is_some() {
+                                return Err(de::Error::duplicate_field("search_aliases"));
+                            }
+                            search_aliases = Some(value.into_strings().ok_or_else(invalid)?);
+                        }
+                        MapKey::Literal => {
+                            if literal.is_some() {
+                                return Err(de::Error::duplicate_field("literal"));
+                            }
+                            literal = Some(value.into_bool().ok_or_else(invalid)?);
+                        }
+                        MapKey::Weight => {
+                            if weight.is_some() {
+                                return Err(de::Error::duplicate_field("weight"));
+                            }
+                            weight = Some(value.into_i32().ok_or_else(invalid)?);
+                        }
+                        MapKey::Separator => {
+                            if separator.is_some() {
+                                return Err(de::Error::duplicate_field("separator"));
+                            }
+                            separator = Some(value.into_bool().ok_or_else(invalid)?);
+                        }
+                        MapKey::CopyTemplate => {
+                            if copy_template.is_some() {
+                                return Err(de::Error::duplicate_field("copy_template"));
+                            }
+                            let template = value.into_string().ok_or_else(invalid)?;
+                            copy_template = Some(CopyTemplate::parse(&*template)?);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+
+                Ok(UnresolvedMap {
+                    extends,
+                    from_command,
+                    direct,
+                    text,
+                    name: display_name,
+                    search_aliases,
+                    literal,
+                    weight,
+                    separator: separator.unwrap_or(false),
+                    match_mode,
+                    copy_template,
+                    display,
+                })
             }
         }
         deserializer.deserialize_map(Visitor)
     }
 }
 
-enum UnresolvedContent {
-    Text(String),
-    Items(UnresolvedItems),
-}
-
 impl<'de> Deserialize<'de> for UnresolvedContent {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct Visitor;
@@ -188,27 +1731,151 @@ impl<'de> Deserialize<'de> for UnresolvedContent {
             }
 
             fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
-                Ok(UnresolvedContent::Text(v.to_owned()))
+                self.visit_string(v.to_owned())
+            }
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(UnresolvedContent::from_text(v))
+            }
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                UnresolvedMap::deserialize(de::value::MapAccessDeserializer::new(map))?
+                    .into_content()
+            }
+        }
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The value of a key that is an option of a map in some maps and the name of an item in others,
+/// kept until the rest of the map is known.
+enum KeyValue {
+    Str(String),
+    Bool(bool),
+    Int(i64),
+    Strings(Vec<String>),
+    Content(UnresolvedContent),
+}
+
+impl KeyValue {
+    /// The item the value is, if it is an item's value.
+    fn into_content(self) -> Option<UnresolvedContent> {
+        match self {
+            Self::Str(text) => Some(UnresolvedContent::from_text(text)),
+            Self::Content(content) => Some(content),
+            Self::Bool(_) | Self::Int(_) | Self::Strings(_) => None,
+        }
+    }
+
+    fn into_string(self) -> Option<String> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn into_strings(self) -> Option<Vec<String>> {
+        match self {
+            Self::Strings(strings) => Some(strings),
+            _ => None,
+        }
+    }
+
+    fn into_bool(self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn into_i32(self) -> Option<i32> {
+        match self {
+            Self::Int(n) => i32::try_from(n).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = KeyValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a UTF-8 string, boolean, integer, list of strings or map of items")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(KeyValue::Str(v.to_owned()))
             }
             fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
-                Ok(UnresolvedContent::Text(v))
+                Ok(KeyValue::Str(v))
+            }
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(KeyValue::Bool(v))
+            }
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(KeyValue::Int(v))
+            }
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map(KeyValue::Int)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+            }
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut strings = Vec::new();
+                while let Some(s) = seq.next_element::<String>()? {
+                    strings.push(s);
+                }
+                Ok(KeyValue::Strings(strings))
             }
             fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
-                Ok(UnresolvedContent::Items(UnresolvedItems::deserialize(
-                    de::value::MapAccessDeserializer::new(map),
-                )?))
+                UnresolvedMap::deserialize(de::value::MapAccessDeserializer::new(map))?
+                    .into_content()
+                    .map(KeyValue::Content)
             }
         }
         deserializer.deserialize_any(Visitor)
     }
 }
 
+/// Files compiled into the library with the `embedded-data` feature,
+/// used when they are not found in any of the configuration directories.
+///
+/// `config.ron` is only embedded as a default that includes the generated files,
+/// which must have been generated into `etc` by `cargo dev generate` before building.
+#[cfg(feature = "embedded-data")]
+const EMBEDDED: &[(&str, &str)] = &[
+    (
+        "config.ron",
+        r#"Config(root: { "extends": ["codepoints.ron", "emojis.ron"] })"#,
+    ),
+    (
+        "codepoints.ron",
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/etc/codepoints.ron")),
+    ),
+    (
+        "emojis.ron",
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/etc/emojis.ron")),
+    ),
+];
+#[cfg(not(feature = "embedded-data"))]
+const EMBEDDED: &[(&str, &str)] = &[];
+
+/// Look up a relative path in [`EMBEDDED`].
+fn embedded(path: impl AsRef<Path>) -> Option<&'static str> {
+    let path = path.as_ref();
+    EMBEDDED
+        .iter()
+        .find(|(name, _)| path == Path::new(name))
+        .map(|&(_, contents)| contents)
+}
+
 struct Paths {
     bases: Vec<PathBuf>,
 }
 
 impl Paths {
-    fn from_env() -> anyhow::Result<Self> {
+    fn from_env() -> Result<Self, Error> {
         let user_config = env::var_os("XDG_CONFIG_HOME")
             .map(PathBuf::from)
             .or_else(|| {
@@ -216,7 +1883,7 @@ impl Paths {
                 home.push(".config");
                 Some(home)
             })
-            .context("$HOME environment variable is not set")?;
+            .ok_or(Error::NoHome)?;
 
         let config_dirs = env::var_os("XDG_CONFIG_DIRS").unwrap_or_else(|| "/etc/xdg".into());
         let config_dirs = config_dirs
@@ -224,22 +1891,38 @@ impl Paths {
             .split(|&byte| byte == b':')
             .map(|dir| PathBuf::from(OsString::from_vec(dir.to_owned())));
 
-        let bases = [user_config]
+        let bases = [user_config].into_iter().chain(config_dirs).map(|mut dir| {
+            dir.push("rofi-unicode");
+            dir
+        });
+
+        Ok(Self::new(bases))
+    }
+
+    fn new(bases: impl IntoIterator<Item = PathBuf>) -> Self {
+        let bases = bases
             .into_iter()
-            .chain(config_dirs)
-            .map(|mut dir| {
-                dir.push("rofi-unicode");
-                dir
-            })
+            // Resolve symlinks once up front so that every include is read from its real path.
+            // Directories that don't exist yet are kept as they are.
+            .map(|dir| fs::canonicalize(&*dir).unwrap_or(dir))
             .collect();
-
-        Ok(Self { bases })
+        Self { bases }
     }
 
     fn config_home(&self) -> &Path {
         &*self.bases[0]
     }
 
+    /// Whether a path resolves to a file, as it would be by [`Self::read_to_string`].
+    fn exists(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            path.is_file()
+        } else {
+            self.bases.iter().any(|base| base.join(path).is_file()) || embedded(path).is_some()
+        }
+    }
+
     fn read_to_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
         let path = path.as_ref();
         if path.is_absolute() {
@@ -256,7 +1939,489 @@ impl Paths {
                     }
                 }
             }
-            Err(anyhow!("could not resolve path {}", path.display()))
+            match embedded(path) {
+                Some(contents) => Ok(contents.to_owned()),
+                None => Err(anyhow!("could not resolve path {}", path.display())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write files into a new temporary directory, for reading a configuration from.
+    fn temp_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rofi-unicode-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&*dir);
+        fs::create_dir_all(&*dir).unwrap();
+        for (path, contents) in files {
+            fs::write(dir.join(path), contents).unwrap();
+        }
+        dir
+    }
+
+    /// Read the `config.ron` of a temporary directory written by [`temp_dir`].
+    fn read_files(name: &str, files: &[(&str, &str)]) -> Result<Config, Error> {
+        read_timed(vec![temp_dir(name, files)], "config.ron").map(|(config, _)| config)
+    }
+
+    #[test]
+    fn comments_and_trailing_commas() {
+        let base = from_ron::<Base>(include_str!("../tests/fixtures/comments/config.ron")).unwrap();
+        assert!(base.show_random);
+        assert_eq!(base.root.extends, ["include.ron"]);
+        assert_eq!(base.root.direct.len(), 1);
+
+        let include =
+            from_ron::<UnresolvedItems>(include_str!("../tests/fixtures/comments/include.ron"))
+                .unwrap();
+        let names = include
+            .direct
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Heart", "Star"]);
+    }
+
+    #[test]
+    fn circular_include() {
+        let res = read_files(
+            "circular-include",
+            &[
+                ("config.ron", r#"Config(root: { "extends": ["a.ron"] })"#),
+                ("a.ron", r#"{ "extends": ["b.ron"] }"#),
+                ("b.ron", r#"{ "extends": ["a.ron"] }"#),
+            ],
+        );
+        match res {
+            Err(Error::Resolve(e)) => {
+                let message = e.to_string();
+                assert!(message.contains("a.ron -> b.ron -> a.ron"), "{message}");
+            }
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("circular include was accepted"),
+        }
+    }
+
+    #[test]
+    fn symlinked_config_dir() {
+        let dir = temp_dir(
+            "symlinked-config-dir",
+            &[
+                (
+                    "config.ron",
+                    r#"Config(root: { "extends": ["include.ron"] })"#,
+                ),
+                ("include.ron", r#"{ "Heart": "❤" }"#),
+            ],
+        );
+        let link = dir.with_extension("link");
+        let _ = fs::remove_file(&*link);
+        std::os::unix::fs::symlink(&*dir, &*link).unwrap();
+
+        let paths = Paths::new([link.clone()]);
+        assert_eq!(paths.bases, [fs::canonicalize(&*dir).unwrap()]);
+
+        let (config, _) = read_timed(vec![link], "config.ron").unwrap();
+        assert_eq!(config.root.items.len(), 1);
+        assert_eq!(config.root.items[0].name, "Heart");
+    }
+
+    #[test]
+    fn repeated_includes() {
+        // A chain of files that each include the next one twice,
+        // which would take 2^64 steps to walk if nothing was skipped.
+        let mut files = (0..64)
+            .map(|i| {
+                let next = i + 1;
+                let contents = format!(r#"{{ "extends": ["{next}.ron", "{next}.ron"] }}"#);
+                (format!("{i}.ron"), contents)
+            })
+            .collect::<Vec<_>>();
+        files.push(("64.ron".to_owned(), "{}".to_owned()));
+        files.push((
+            "a.ron".to_owned(),
+            r#"{ "extends": ["0.ron"], "Nested": { "x": "x" } }"#.to_owned(),
+        ));
+        let files = files
+            .iter()
+            .map(|(path, contents)| (&**path, &**contents))
+            .collect::<Vec<_>>();
+        let paths = Paths::new([temp_dir("repeated-includes", &*files)]);
+
+        let root = from_ron::<UnresolvedItems>(
+            r#"{ "extends": ["a.ron", "a.ron", "a.ron#Nested", "missing.ron"] }"#,
+        )
+        .unwrap();
+        let mut includes = HashMap::new();
+        let mut missing = Vec::new();
+        find_missing_includes(
+            &paths,
+            &root,
+            &mut vec!["config.ron".to_owned()],
+            1000,
+            &mut HashSet::new(),
+            &mut includes,
+            &mut missing,
+        )
+        .unwrap();
+        assert_eq!(missing, ["missing.ron (included by config.ron)"]);
+        // Every include path was parsed once, ready to be resolved.
+        assert_eq!(includes.len(), 65 + 2);
+        assert_eq!(includes["a.ron#Nested"].direct[0].name, "x");
+    }
+
+    #[test]
+    fn merge_system_config() {
+        let system = temp_dir(
+            "merge-system-config-system",
+            &[(
+                "config.ron",
+                r#"Config(
+                        root: { "match_mode": Prefix, "display": Glyphs, "Heart": "❤" },
+                        show_random: true,
+                    )"#,
+            )],
+        );
+        let read = |name, config_ron| {
+            let user = temp_dir(name, &[("config.ron", config_ron)]);
+            read_timed(vec![user, system.clone()], "config.ron")
+                .unwrap()
+                .0
+        };
+
+        let merged = read(
+            "merge-system-config-user",
+            r#"Config(root: { "display": Rows, "Star": "★" }, merge_system_config: true)"#,
+        );
+        let names = merged
+            .root
+            .items
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Heart", "Star"]);
+        // List options are taken from the system root where the user's doesn't set them.
+        assert_eq!(merged.root.match_mode, Some(MatchMode::Prefix));
+        assert_eq!(merged.root.display, Some(DisplayMode::Rows));
+        // Other options are not.
+        assert!(!merged.show_random);
+
+        let unmerged = read(
+            "merge-system-config-unmerged",
+            r#"Config(root: { "Star": "★" })"#,
+        );
+        assert_eq!(unmerged.root.items.len(), 1);
+        assert_eq!(unmerged.root.items[0].name, "Star");
+    }
+
+    #[test]
+    fn file_included_twice() {
+        let config = read_files(
+            "file-included-twice",
+            &[
+                (
+                    "config.ron",
+                    r#"Config(root: { "extends": ["a.ron", "a.ron"] })"#,
+                ),
+                ("a.ron", r#"{ "x": "x" }"#),
+            ],
+        )
+        .unwrap();
+        let names = config
+            .root
+            .items
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x", "x"]);
+    }
+
+    #[test]
+    fn error_variants() {
+        let dir = temp_dir("error-variants", &[]);
+        match read_timed(vec![dir], "config.ron") {
+            Err(Error::Missing(path)) => assert!(path.ends_with("config.ron")),
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("missing file was accepted"),
+        }
+
+        let res = read_files("error-variants-parse", &[("config.ron", "Config(root: {")]);
+        match res {
+            Err(Error::Parse { path, .. }) => assert!(path.ends_with("config.ron")),
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("invalid RON was accepted"),
+        }
+
+        let res = read_files(
+            "error-variants-missing-include",
+            &[("config.ron", r#"Config(root: { "extends": ["a.ron"] })"#)],
+        );
+        match res {
+            Err(Error::MissingIncludes(missing)) => {
+                assert_eq!(missing, ["a.ron (included by config.ron)"]);
+            }
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("missing include was accepted"),
+        }
+
+        let res = read_files(
+            "error-variants-parse-include",
+            &[
+                ("config.ron", r#"Config(root: { "extends": ["a.ron"] })"#),
+                ("a.ron", r#"{ "extends": ["b.ron"] }"#),
+                ("b.ron", r#"{ "Heart": }"#),
+            ],
+        );
+        match res {
+            Err(Error::ParseInclude {
+                path, included_by, ..
+            }) => {
+                assert_eq!(path, "b.ron");
+                assert_eq!(included_by, "config.ron -> a.ron");
+            }
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("invalid included file was accepted"),
+        }
+    }
+
+    #[test]
+    fn blank_names() {
+        let config_ron = |strict: bool| {
+            format!(
+                r#"Config(
+                    root: {{ "<b> </b>": "x", "Heart": "❤", " ": {{ "separator": true }} }},
+                    strict: {strict},
+                )"#,
+            )
+        };
+
+        let config = from_str(&*config_ron(false)).unwrap();
+        assert_eq!(config.root.items.len(), 3);
+        assert_eq!(
+            config.warnings,
+            [r#"item name "<b> </b>" in config.ron displays as a blank row"#],
+        );
+
+        match from_str(&*config_ron(true)) {
+            Err(Error::Resolve(e)) => {
+                let message = e.to_string();
+                assert!(message.contains("displays as a blank row"), "{message}");
+            }
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("blank name was accepted in strict mode"),
         }
     }
+
+    #[test]
+    fn clipboard_targets() {
+        let config = from_str("Config(root: {})").unwrap();
+        assert_eq!(config.clipboard_selections, [Selection::Clipboard]);
+
+        let config = from_str(r#"Config(root: {}, clipboard_targets: ["clipboard", "primary"])"#);
+        assert_eq!(
+            config.unwrap().clipboard_selections,
+            [Selection::Clipboard, Selection::Primary],
+        );
+
+        for targets in [r#"["clipboard", "Primary"]"#, "[]"] {
+            let res = from_str(&*format!(
+                "Config(root: {{}}, clipboard_targets: {targets})"
+            ));
+            match res {
+                Err(Error::InvalidClipboardTargets(_)) => {}
+                Err(e) => panic!("wrong error: {e}"),
+                Ok(_) => panic!("clipboard targets {targets} were accepted"),
+            }
+        }
+    }
+
+    #[test]
+    fn max_name_len() {
+        let config = from_str(
+            r#"Config(
+                root: {
+                    "Short": "a",
+                    "<b>Bold</b> and <i>ital&amp;ic</i> name": "b",
+                    "Ünïcödé nämé": "c",
+                },
+                max_name_len: Some(10),
+            )"#,
+        )
+        .unwrap();
+        let items = &config.root.items;
+
+        assert_eq!(items[0].name, "Short");
+        assert!(items[0].search_aliases.is_empty());
+
+        // The open tag is closed, and the entity is not split.
+        assert_eq!(items[1].name_markup, "<b>Bold</b> and <i>i…</i>");
+        assert_eq!(items[1].name, "Bold and i…");
+        assert_eq!(items[1].search_aliases, ["Bold and ital&ic name"]);
+
+        assert_eq!(items[2].name, "Ünïcödé nä…");
+        assert_eq!(items[2].search_aliases, ["Ünïcödé nämé"]);
+
+        assert_eq!(truncate_markup("a&amp;b&lt;c", 3).unwrap(), "a&amp;b…");
+        assert_eq!(truncate_markup("<b>short</b>", 5), None);
+    }
+
+    #[test]
+    fn option_names_as_items() {
+        let config = from_str(
+            r#"Config(root: {
+                "Words": {
+                    "name": "Name",
+                    "weight": "Weight",
+                    "search_aliases": { "literal": "Literal" },
+                    "separator": "Separator",
+                    "copy_template": "Copy template",
+                },
+                "Red heart": {
+                    "text": "❤",
+                    "name": "Heart",
+                    "search_aliases": ["love"],
+                    "weight": 2,
+                    "literal": true,
+                },
+                "Quoted": {
+                    "copy_template": "'{}'",
+                    "───": { "separator": true },
+                    "Heart": "❤",
+                },
+            })"#,
+        )
+        .unwrap();
+        let list = |item: &Item| match &item.content {
+            Content::Items(list) => list,
+            _ => panic!("{:?} is not a submenu", item.name),
+        };
+        let names = |list: &List| {
+            list.items
+                .iter()
+                .map(|item| item.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let root = &config.root.items;
+
+        // In the order they were written
+        let words = list(&root[0]);
+        assert_eq!(
+            names(words),
+            [
+                "name",
+                "weight",
+                "search_aliases",
+                "separator",
+                "copy_template"
+            ],
+        );
+        assert_eq!(names(list(&words.items[2])), ["literal"]);
+        assert!(words.copy_template.is_none());
+
+        assert_eq!(root[1].name, "Heart");
+        assert_eq!(root[1].search_aliases, ["love"]);
+        assert_eq!(root[1].weight, 2);
+        assert!(matches!(&root[1].content, Content::Text(text) if text == "❤"));
+
+        let quoted = list(&root[2]);
+        assert!(quoted.copy_template.is_some());
+        assert!(matches!(quoted.items[0].content, Content::Separator));
+        assert_eq!(quoted.items[1].name, "Heart");
+
+        let res = from_str(r#"Config(root: { "Heart": { "text": "❤", "weight": "heavy" } })"#);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn option_names_alongside_extends() {
+        let config = read_files(
+            "option-names-alongside-extends",
+            &[
+                (
+                    "config.ron",
+                    r#"Config(root: {
+                        "Hearts": { "name": "Name", "extends": ["hearts.ron"], "weight": "Weight" },
+                    })"#,
+                ),
+                ("hearts.ron", r#"{ "Heart": "❤" }"#),
+            ],
+        )
+        .unwrap();
+        let hearts = match &config.root.items[0].content {
+            Content::Items(list) => list,
+            _ => panic!("Hearts is not a submenu"),
+        };
+        let names = hearts
+            .items
+            .iter()
+            .map(|item| &*item.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["name", "weight", "Heart"]);
+    }
+
+    #[test]
+    fn text_as_item() {
+        let config = from_str(
+            r#"Config(root: {
+                "Letters": { "text": "T", "Heart": "❤" },
+                "Nested": { "text": { "Star": "★" } },
+                "Heart": { "text": "❤", "name": "Red heart" },
+            })"#,
+        )
+        .unwrap();
+        let names = |item: &Item| match &item.content {
+            Content::Items(list) => list
+                .items
+                .iter()
+                .map(|item| item.name.clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("{:?} is not a submenu", item.name),
+        };
+        let root = &config.root.items;
+        assert_eq!(names(&root[0]), ["text", "Heart"]);
+        assert_eq!(names(&root[1]), ["text"]);
+        assert_eq!(root[2].name, "Red heart");
+        assert!(matches!(&root[2].content, Content::Text(text) if text == "❤"));
+
+        let items = parse_file_items(r#"{ "text": "T" }"#).unwrap();
+        assert_eq!(items.items.len(), 1);
+        assert_eq!(items.items[0].name, "text");
+        assert!(matches!(&items.items[0].content, FileContent::Text(text) if text == "T"));
+
+        assert!(parse_file_items(r#"{ "text": "T", "name": "Name" }"#).is_err());
+    }
+
+    #[test]
+    fn max_results_with_fuzzy() {
+        let config_ron = |nested_match_mode: &str| {
+            format!(
+                r#"Config(
+                    root: {{
+                        "match_mode": Substring,
+                        "Arrows": {{ "match_mode": {nested_match_mode}, "Left": "←" }},
+                    }},
+                    max_results: Some(2),
+                )"#,
+            )
+        };
+        let config = from_str(&*config_ron("Prefix")).unwrap();
+        assert!(config.warnings.is_empty());
+
+        let config = from_str(&*config_ron("Fuzzy")).unwrap();
+        assert_eq!(config.warnings.len(), 1);
+        assert!(
+            config.warnings[0].contains("max_results"),
+            "{:?}",
+            config.warnings
+        );
+
+        // The default is also matched by Rofi.
+        let config = from_str(r#"Config(root: { "Heart": "❤" }, max_results: Some(2))"#).unwrap();
+        assert_eq!(config.warnings.len(), 1);
+        let config = from_str(r#"Config(root: { "Heart": "❤" })"#).unwrap();
+        assert!(config.warnings.is_empty());
+    }
 }
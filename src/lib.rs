@@ -1,30 +1,131 @@
 use ::{
     anyhow::Context as _,
     rofi_mode::Mode,
-    std::{io::Write, process},
+    std::{
+        cmp::Reverse,
+        collections::HashMap,
+        env, fmt,
+        fs::{self, OpenOptions},
+        io::{self, Write as _},
+        ops::RangeInclusive,
+        process, thread,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    unicode_normalization::UnicodeNormalization as _,
 };
 
-mod config;
+/// Print a diagnostic line to stderr.
+/// Since Rofi discards stderr, the line is also appended to the file at `$ROFI_UNICODE_LOG`
+/// if it is set.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log_line(format_args!($($arg)*))
+    };
+}
+
+pub mod clipboard;
+pub mod config;
 
 rofi_mode::export_mode!(Unicode);
 
 struct Unicode {
     lists: Vec<List>,
     active_list: usize,
+    show_random: bool,
+    append_newline: bool,
+    copy_normalization: config::Normalization,
+    trim_copy: bool,
+    allow_custom_input: bool,
+    confirm_invisible: bool,
+    stdout_fallback: bool,
+    after_copy: config::AfterCopy,
+    cancel_behavior: config::CancelBehavior,
+    max_results: Option<usize>,
+    /// Map from the number of each custom command Rofi sends to the action it is bound to.
+    key_actions: HashMap<u8, config::KeyAction>,
+    clipboard: clipboard::Clipboard,
+    reference_url: String,
+    /// The current input, in lowercase.
+    input: String,
+    /// The range of code points written in the input,
+    /// if it is of the form `XXXX..YYYY` or `XXXX..`.
+    input_range: Option<RangeInclusive<u32>>,
+    /// The line before which the first `max_results` matches of the current search are,
+    /// if there are more matches than that.
+    result_limit: Option<usize>,
+    /// Map from the text of every text item to the first item that copies it.
+    glyphs: HashMap<String, ItemIndex>,
+    /// The characters of the decoded input, if a string is currently being decoded.
+    decoded: Option<Box<[char]>>,
+    /// The invisible text that was just selected and is waiting to be selected again to be copied.
+    confirming: Option<String>,
 }
 
 impl Unicode {
     fn active_list(&self) -> &List {
         &self.lists[self.active_list]
     }
-    fn item(&self, i: usize) -> &Item {
-        &self.active_list().items[i]
+    fn item(&self, index: ItemIndex) -> &Item {
+        &self.lists[index.list].items[index.index]
+    }
+    fn entry(&self, line: usize) -> Entry<'_> {
+        if let Some(decoded) = &self.decoded {
+            let character = decoded[line];
+            let mut buf = [0; 4];
+            let item = self
+                .glyphs
+                .get(&*character.encode_utf8(&mut buf))
+                .map(|&index| self.item(index));
+            return Entry::Decoded(character, item);
+        }
+        if self.active_list().items.is_empty() {
+            return Entry::Empty;
+        }
+        let line = if self.show_random {
+            match line.checked_sub(1) {
+                Some(line) => line,
+                None => return Entry::Random,
+            }
+        } else {
+            line
+        };
+        let list = self.active_list();
+        // Rofi has no way for a mode to rank matches, but it shows them in the order of their lines
+        // (unless `-sort` is on), so weighted items are promoted by giving them earlier lines.
+        let index = if self.input.is_empty() {
+            line
+        } else {
+            list.search_order[line]
+        };
+        Entry::Item(&list.items[index])
     }
 }
 
+/// A row shown by Rofi.
+enum Entry<'a> {
+    /// The entry that copies a random character from the active list.
+    Random,
+    /// The placeholder shown in place of the items of an empty submenu.
+    Empty,
+    Item(&'a Item),
+    /// A single character of the decoded input, along with the item that copies it if there is one.
+    Decoded(char, Option<&'a Item>),
+}
+
+const RANDOM_ENTRY_NAME: &str = "(random character)";
+const EMPTY_ENTRY_NAME: &str = "(empty)";
+
 struct List {
     index: Option<ItemIndex>,
     items: Box<[Item]>,
+    /// The indices of `items` in the order they are shown while searching:
+    /// by descending weight, then in the order they were configured.
+    search_order: Box<[usize]>,
+    match_mode: config::MatchMode,
+    /// The template that text copied from the list is put through,
+    /// inherited from the parent list if the list doesn't have its own.
+    copy_template: Option<config::CopyTemplate>,
+    display: config::DisplayMode,
 }
 
 /// An index of a specific [`Item`] in the [`Unicode`].
@@ -40,62 +141,176 @@ struct Item {
     name: String,
     name_attributes: Vec<pango::Attribute>,
     name_markup: String,
+    /// The text that searches are matched against:
+    /// the name followed by any search aliases.
+    search_text: String,
+    /// `name` in lowercase, precomputed so that it isn't recomputed on every keystroke.
+    folded_name: String,
+    /// `search_text` in lowercase, likewise.
+    folded_search_text: String,
     content: Content,
 }
 
 enum Content {
     Text(String),
     List(usize),
+    /// A row that only separates the rows around it.
+    Separator,
 }
 
 impl Unicode {
     fn try_init() -> anyhow::Result<Self> {
         let config = config::read().context("failed to read configuration")?;
+        let unicode = Self::from_config(config)?;
+
+        // Checked once in the background, since it can take as long as the clipboard timeout.
+        // The check is best-effort, and only worth its cost when copying with `xclip`.
+        if unicode.clipboard.command.is_none() {
+            let clipboard = unicode.clipboard.clone();
+            thread::spawn(move || {
+                if clipboard.manager_running() == Some(false) {
+                    log!(
+                        "warning: no clipboard manager is running, \
+                        so copied text is lost if xclip exits before it is pasted"
+                    );
+                }
+            });
+        }
+
+        Ok(unicode)
+    }
 
+    /// Build the mode from an already resolved configuration,
+    /// without touching the filesystem or Rofi.
+    fn from_config(config: config::Config) -> anyhow::Result<Self> {
         let mut lists = Vec::new();
-        let active_list = register_items(config.root, &mut lists, None);
+        let mut glyphs = HashMap::new();
+        let active_list = register_items(
+            config.root,
+            &mut lists,
+            &mut glyphs,
+            None,
+            &mut Vec::new(),
+            config.max_depth,
+        )?;
         assert_eq!(active_list, 0);
+        log!("loaded configuration with {} lists", lists.len());
+        for warning in &config.warnings {
+            log!("warning: {warning}");
+        }
 
-        Ok(Self { lists, active_list })
+        Ok(Self {
+            lists,
+            active_list,
+            show_random: config.show_random,
+            append_newline: config.append_newline,
+            copy_normalization: config.copy_normalization,
+            trim_copy: config.trim_copy,
+            allow_custom_input: config.allow_custom_input,
+            confirm_invisible: config.confirm_invisible,
+            stdout_fallback: config.stdout_fallback,
+            after_copy: config.after_copy,
+            cancel_behavior: config.cancel_behavior,
+            max_results: config.max_results,
+            key_actions: config.key_actions,
+            clipboard: clipboard::Clipboard {
+                selections: config.clipboard_selections,
+                timeout: config.clipboard_timeout,
+                command: config.clipboard_command,
+                ..clipboard::Clipboard::default()
+            },
+            reference_url: config.reference_url,
+            input: String::new(),
+            input_range: None,
+            result_limit: None,
+            glyphs,
+            decoded: None,
+            confirming: None,
+        })
     }
 }
 
 fn register_items(
-    items: Vec<config::Item>,
+    list: config::List,
     lists: &mut Vec<List>,
+    glyphs: &mut HashMap<String, ItemIndex>,
     index: Option<ItemIndex>,
-) -> usize {
+    path: &mut Vec<String>,
+    max_depth: usize,
+) -> anyhow::Result<usize> {
+    if path.len() > max_depth {
+        anyhow::bail!(
+            "maximum nesting depth of {max_depth} exceeded at {:?}",
+            path.join(" / ")
+        );
+    }
+
+    let mut search_order = (0..list.items.len()).collect::<Vec<_>>();
+    // The sort is stable, so items of equal weight keep their order.
+    search_order.sort_by_key(|&i| Reverse(list.items[i].weight));
+
     let list_index = lists.len();
     lists.push(List {
         index,
         items: Box::new([]),
+        search_order: Box::new([]),
+        match_mode: list.match_mode.unwrap_or(config::MatchMode::Fuzzy),
+        display: list.display.unwrap_or(config::DisplayMode::Rows),
+        copy_template: list
+            .copy_template
+            .or_else(|| index.and_then(|index| lists[index.list].copy_template.clone())),
     });
 
-    lists[list_index].items = items
+    lists[list_index].search_order = search_order.into_boxed_slice();
+    lists[list_index].items = list
+        .items
         .into_iter()
         .enumerate()
-        .map(|(index, config_item)| Item {
-            name: config_item.name,
-            name_attributes: config_item.name_attributes,
-            name_markup: config_item.name_markup,
-            content: match config_item.content {
-                config::Content::Text(text) => Content::Text(text),
+        .map(|(index, config_item)| {
+            let content = match config_item.content {
+                config::Content::Text(text) => {
+                    glyphs.entry(text.clone()).or_insert(ItemIndex {
+                        list: list_index,
+                        index,
+                    });
+                    Content::Text(text)
+                }
                 config::Content::Items(nested) => {
+                    path.push(config_item.name.clone());
                     let index = register_items(
                         nested,
                         lists,
+                        glyphs,
                         Some(ItemIndex {
                             list: list_index,
                             index,
                         }),
-                    );
+                        path,
+                        max_depth,
+                    )?;
+                    path.pop();
                     Content::List(index)
                 }
-            },
+                config::Content::Separator => Content::Separator,
+            };
+            let mut search_text = config_item.name.clone();
+            for alias in config_item.search_aliases {
+                search_text.push(' ');
+                search_text.push_str(&*alias);
+            }
+            Ok(Item {
+                folded_name: config_item.name.to_lowercase(),
+                folded_search_text: search_text.to_lowercase(),
+                name: config_item.name,
+                name_attributes: config_item.name_attributes,
+                name_markup: config_item.name_markup,
+                search_text,
+                content,
+            })
         })
-        .collect();
+        .collect::<anyhow::Result<_>>()?;
 
-    list_index
+    Ok(list_index)
 }
 
 impl Mode<'_> for Unicode {
@@ -103,27 +318,73 @@ impl Mode<'_> for Unicode {
     const DISPLAY_NAME: &'static str = "unicode\0";
 
     fn init(_api: rofi_mode::Api<'_>) -> Result<Self, ()> {
-        Self::try_init().map_err(|e| eprintln!("Error: {e:?}"))
+        Self::try_init().map_err(|e| log!("Error: {e:?}"))
     }
 
     fn entries(&mut self) -> usize {
-        self.active_list().items.len()
+        if let Some(decoded) = &self.decoded {
+            return decoded.len();
+        }
+        let list = self.active_list();
+        if list.items.is_empty() {
+            // The root list has no placeholder, as it shows a message instead.
+            usize::from(list.index.is_some())
+        } else {
+            list.items.len() + usize::from(self.show_random)
+        }
     }
 
     fn entry_attributes(&self, line: usize) -> rofi_mode::Attributes {
-        self.item(line).name_attributes.iter().cloned().collect()
+        match self.entry(line) {
+            Entry::Random | Entry::Empty => rofi_mode::Attributes::new(),
+            // The attributes index into the name, which isn't shown.
+            Entry::Item(Item {
+                content: Content::Text(_),
+                ..
+            }) if self.active_list().display == config::DisplayMode::Glyphs => {
+                rofi_mode::Attributes::new()
+            }
+            Entry::Item(item) | Entry::Decoded(_, Some(item)) => {
+                item.name_attributes.iter().cloned().collect()
+            }
+            Entry::Decoded(_, None) => rofi_mode::Attributes::new(),
+        }
     }
 
     fn entry_content(&self, line: usize) -> rofi_mode::String {
-        let item = self.item(line);
-        match item.content {
-            Content::Text(_) => rofi_mode::String::from(&*item.name),
+        let item = match self.entry(line) {
+            Entry::Random => return rofi_mode::String::from(RANDOM_ENTRY_NAME),
+            Entry::Empty => return rofi_mode::String::from(EMPTY_ENTRY_NAME),
+            Entry::Item(item) => item,
+            Entry::Decoded(_, Some(item)) => return rofi_mode::String::from(&*item.name),
+            Entry::Decoded(character, None) => {
+                return rofi_mode::format!("U+{:04X}\t{character}", u32::from(character));
+            }
+        };
+        match &item.content {
+            Content::Text(text) if self.active_list().display == config::DisplayMode::Glyphs => {
+                rofi_mode::String::from(&**text)
+            }
+            Content::Text(_) | Content::Separator => rofi_mode::String::from(&*item.name),
             Content::List(_) => rofi_mode::format!("{}/", item.name),
         }
     }
 
+    /// Submenus complete to their name followed by `/`, like they are displayed.
+    /// Completing again once the input is exactly that enters the submenu,
+    /// so that pressing Rofi's completion key repeatedly drills down the hierarchy.
     fn completed(&self, line: usize) -> rofi_mode::String {
-        rofi_mode::String::from(&*self.item(line).name)
+        match self.entry(line) {
+            Entry::Random => rofi_mode::String::from(RANDOM_ENTRY_NAME),
+            Entry::Empty => rofi_mode::String::new(),
+            Entry::Item(Item {
+                name,
+                content: Content::List(_),
+                ..
+            }) => rofi_mode::format!("{name}/"),
+            Entry::Item(item) => rofi_mode::String::from(&*item.name),
+            Entry::Decoded(..) => self.entry_content(line),
+        }
     }
 
     fn react(
@@ -131,9 +392,16 @@ impl Mode<'_> for Unicode {
         event: rofi_mode::Event,
         input: &mut rofi_mode::String,
     ) -> rofi_mode::Action {
+        // Doing anything but selecting the same item again cancels the confirmation.
+        let confirming = self.confirming.take();
         match event {
             rofi_mode::Event::Cancel { .. } => {
-                if let Some(index) = self.active_list().index {
+                if self.cancel_behavior == config::CancelBehavior::Exit {
+                    rofi_mode::Action::Exit
+                } else if self.decoded.take().is_some() {
+                    input.clear();
+                    rofi_mode::Action::Reload
+                } else if let Some(index) = self.active_list().index {
                     self.active_list = index.list;
                     input.clear();
                     rofi_mode::Action::Reload
@@ -141,39 +409,133 @@ impl Mode<'_> for Unicode {
                     rofi_mode::Action::Exit
                 }
             }
-            rofi_mode::Event::Ok { selected, .. } => match &self.item(selected).content {
-                Content::Text(data) => {
-                    if let Err(e) = clipboard_copy(&*data) {
-                        eprintln!("failed to copy text to clipboard: {e:?}");
+            rofi_mode::Event::Ok { selected, .. } => match self.entry(selected) {
+                Entry::Random => {
+                    let mut texts = Vec::new();
+                    self.texts_in(self.active_list, &mut texts);
+                    if texts.is_empty() {
                         return rofi_mode::Action::Reload;
                     }
-                    rofi_mode::Action::Exit
+                    let (text, template) = texts[random_index(texts.len())];
+                    self.copy(text, template)
                 }
-                Content::List(index) => {
-                    self.active_list = *index;
-                    rofi_mode::Action::Reload
+                Entry::Empty => rofi_mode::Action::Reload,
+                Entry::Decoded(character, _) => {
+                    let mut buf = [0; 4];
+                    self.copy(character.encode_utf8(&mut buf), None)
                 }
+                Entry::Item(item) => match &item.content {
+                    Content::Text(data) => {
+                        if self.confirm_invisible
+                            && is_invisible(data)
+                            && confirming.as_ref() != Some(data)
+                        {
+                            self.confirming = Some(data.clone());
+                            return rofi_mode::Action::Reload;
+                        }
+                        self.copy(data, self.active_list().copy_template.as_ref())
+                    }
+                    Content::List(index) => {
+                        self.active_list = *index;
+                        rofi_mode::Action::Reload
+                    }
+                    Content::Separator => rofi_mode::Action::Reload,
+                },
             },
             rofi_mode::Event::Complete {
                 selected: Some(selected),
             } => {
+                let completed = self.completed(selected);
+                let submenu = match self.entry(selected) {
+                    Entry::Item(Item {
+                        content: Content::List(index),
+                        ..
+                    }) => Some(*index),
+                    _ => None,
+                };
+                if let Some(index) = submenu.filter(|_| **input == *completed) {
+                    self.active_list = index;
+                    input.clear();
+                    return rofi_mode::Action::Reload;
+                }
                 input.clear();
-                input.push_str(&*self.item(selected).name);
+                input.push_str(&*completed);
+                rofi_mode::Action::Reload
+            }
+            rofi_mode::Event::Complete { selected: None } => {
+                // A completed submenu name might not match its own submenu,
+                // since the `/` is not part of the name.
+                if let Some(index) = input
+                    .strip_suffix('/')
+                    .and_then(|name| self.submenu_named(name))
+                {
+                    self.active_list = index;
+                    input.clear();
+                }
+                rofi_mode::Action::Reload
+            }
+            rofi_mode::Event::CustomInput { alt: false, .. } if self.allow_custom_input => {
+                if input.is_empty() {
+                    return rofi_mode::Action::Reload;
+                }
+                self.copy(input, None)
+            }
+            rofi_mode::Event::CustomInput { .. } => {
+                let mut buf = [0; 4];
+                if input
+                    .chars()
+                    .any(|c| self.glyphs.contains_key(&*c.encode_utf8(&mut buf)))
+                {
+                    self.decoded = Some(input.chars().collect());
+                    input.clear();
+                }
                 rofi_mode::Action::Reload
             }
-            rofi_mode::Event::CustomInput { .. }
-            | rofi_mode::Event::Complete { selected: None }
-            | rofi_mode::Event::DeleteEntry { .. }
-            | rofi_mode::Event::CustomCommand { .. } => rofi_mode::Action::Reload,
+            rofi_mode::Event::CustomCommand { number, selected } => {
+                match self.key_actions.get(&number) {
+                    Some(&action) => self.react_to_key(action, selected, input),
+                    None => rofi_mode::Action::Reload,
+                }
+            }
+            rofi_mode::Event::DeleteEntry { .. } => rofi_mode::Action::Reload,
         }
     }
 
     fn matches(&self, line: usize, matcher: rofi_mode::Matcher<'_>) -> bool {
-        matcher.matches(&*self.item(line).name)
+        let item = match self.entry(line) {
+            Entry::Random => return matcher.matches(RANDOM_ENTRY_NAME),
+            Entry::Empty | Entry::Decoded(..) => return true,
+            Entry::Item(item) => item,
+        };
+        if self.result_limit.map_or(false, |limit| line >= limit) {
+            return false;
+        }
+        self.item_matches(item)
+            .unwrap_or_else(|| matcher.matches(&*item.search_text))
+    }
+
+    fn preprocess_input(&mut self, input: &str) -> rofi_mode::String {
+        self.input = input.to_lowercase();
+        self.input_range = parse_range(&*self.input);
+        self.result_limit = match self.max_results {
+            Some(max_results) if !self.input.is_empty() && self.decoded.is_none() => {
+                self.result_limit(max_results)
+            }
+            _ => None,
+        };
+        input.into()
     }
 
     fn message(&mut self) -> rofi_mode::String {
-        let mut index = self.active_list().index;
+        if self.decoded.is_some() {
+            return rofi_mode::String::from("(characters of the input)");
+        }
+        let list = self.active_list();
+        if list.index.is_none() && list.items.is_empty() {
+            return rofi_mode::String::from("(no items configured)");
+        }
+
+        let mut index = list.index;
         let mut parts = Vec::new();
         while let Some(item_index) = index {
             if !parts.is_empty() {
@@ -183,21 +545,710 @@ impl Mode<'_> for Unicode {
             parts.push(&list.items[item_index.index].name_markup);
             index = list.index;
         }
-        parts.into_iter().rev().collect()
+        let mut message = parts.into_iter().rev().collect::<rofi_mode::String>();
+
+        if let Some(text) = &self.confirming {
+            if !message.is_empty() {
+                message.push_str("\n");
+            }
+            let code_points = text
+                .chars()
+                .map(|c| format!("U+{:04X}", u32::from(c)))
+                .collect::<Vec<_>>();
+            message.push_str(&*format!(
+                "<b>{} is invisible; select it again to copy it</b>",
+                code_points.join(" ")
+            ));
+        }
+
+        if let Some(hint) = self.code_point_hint() {
+            if !message.is_empty() {
+                message.push_str("\n");
+            }
+            message.push_str(&*hint);
+        }
+        message
+    }
+}
+
+impl Unicode {
+    /// Find the list of the submenu in the active list with the given name.
+    fn submenu_named(&self, name: &str) -> Option<usize> {
+        self.active_list()
+            .items
+            .iter()
+            .find_map(|item| match item.content {
+                Content::List(index) if item.name == name => Some(index),
+                _ => None,
+            })
+    }
+
+    /// Whether an item of the active list matches the input,
+    /// or `None` if that is decided by Rofi's matcher.
+    fn item_matches(&self, item: &Item) -> Option<bool> {
+        if let Content::Separator = item.content {
+            // Separators are only useful when browsing, not when searching.
+            return Some(self.input.is_empty());
+        }
+        if let Some(range) = &self.input_range {
+            let mut chars = match &item.content {
+                Content::Text(text) => text.chars(),
+                Content::List(_) | Content::Separator => return Some(false),
+            };
+            return Some(match (chars.next(), chars.next()) {
+                (Some(c), None) => range.contains(&u32::from(c)),
+                _ => false,
+            });
+        }
+        match self.active_list().match_mode {
+            config::MatchMode::Prefix => Some(item.folded_name.starts_with(&*self.input)),
+            config::MatchMode::Fuzzy => None,
+            config::MatchMode::Substring => Some(item.folded_search_text.contains(&*self.input)),
+        }
+    }
+
+    /// Find the line after the first `max_results` items of the active list that match the input.
+    ///
+    /// Since Rofi filters lines independently of each other (and possibly in parallel),
+    /// this is worked out up front whenever the input changes,
+    /// at the cost of matching every item an extra time.
+    /// Returns `None` if there are no more matches than that.
+    fn result_limit(&self, max_results: usize) -> Option<usize> {
+        let lines = self.active_list().items.len() + usize::from(self.show_random);
+        let mut found = 0;
+        for line in 0..lines {
+            if let Entry::Item(item) = self.entry(line) {
+                if found == max_results {
+                    return Some(line);
+                }
+                if self.item_matches(item)? {
+                    found += 1;
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Unicode {
+    /// Describe the character whose code point is written in the input, as Pango markup.
+    fn code_point_hint(&self) -> Option<String> {
+        let c = parse_code_point(&*self.input)?;
+        let mut buf = [0; 4];
+        let glyph = &*c.encode_utf8(&mut buf);
+        Some(match self.glyphs.get(glyph) {
+            // Generated names already start with the code point and glyph.
+            Some(&index) => self.item(index).name_markup.clone(),
+            None => format!(
+                "U+{:04X} {}",
+                u32::from(c),
+                pango::glib::markup_escape_text(glyph)
+            ),
+        })
+    }
+}
+
+impl Unicode {
+    /// Collect the text of every text item in a list and all its submenus,
+    /// along with the template of the list it is in.
+    fn texts_in<'a>(
+        &'a self,
+        list: usize,
+        texts: &mut Vec<(&'a str, Option<&'a config::CopyTemplate>)>,
+    ) {
+        let template = self.lists[list].copy_template.as_ref();
+        for item in &*self.lists[list].items {
+            match &item.content {
+                Content::Text(text) => texts.push((text, template)),
+                Content::List(nested) => self.texts_in(*nested, texts),
+                Content::Separator => {}
+            }
+        }
+    }
+}
+
+impl Unicode {
+    /// Open the reference page of a character with `xdg-open`.
+    fn open_reference(&self, character: char) {
+        let url = self
+            .reference_url
+            .replace("{codepoint}", &*format!("{:04X}", u32::from(character)));
+        log!("opening {url}");
+        let res = process::Command::new("xdg-open")
+            .arg(&*url)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn();
+        match res {
+            // Reap the child once it exits without holding up Rofi.
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => log!("failed to run xdg-open: {e}"),
+        }
+    }
+}
+
+impl Unicode {
+    /// React to one of Rofi's custom keys being pressed.
+    fn react_to_key(
+        &mut self,
+        action: config::KeyAction,
+        selected: Option<usize>,
+        input: &mut rofi_mode::String,
+    ) -> rofi_mode::Action {
+        match (action, selected) {
+            (config::KeyAction::Root, _) => {
+                self.decoded = None;
+                self.active_list = 0;
+                input.clear();
+                rofi_mode::Action::Reload
+            }
+            (config::KeyAction::CopyShell, Some(selected)) => match self.entry(selected) {
+                Entry::Item(Item {
+                    content: Content::Text(text),
+                    ..
+                }) => self.copy(&*shell_escape(text), None),
+                Entry::Decoded(character, _) => {
+                    let mut buf = [0; 4];
+                    self.copy(&*shell_escape(character.encode_utf8(&mut buf)), None)
+                }
+                Entry::Item(_) | Entry::Random | Entry::Empty => rofi_mode::Action::Reload,
+            },
+            (config::KeyAction::WriteState, _) => {
+                self.write_state();
+                rofi_mode::Action::Reload
+            }
+            (config::KeyAction::UpTwo, _) => {
+                self.decoded = None;
+                for _ in 0..2 {
+                    if let Some(index) = self.active_list().index {
+                        self.active_list = index.list;
+                    }
+                }
+                input.clear();
+                rofi_mode::Action::Reload
+            }
+            (config::KeyAction::OpenReference, Some(selected)) => {
+                let character = match self.entry(selected) {
+                    Entry::Decoded(character, _) => Some(character),
+                    Entry::Item(Item {
+                        content: Content::Text(text),
+                        ..
+                    }) => {
+                        let mut chars = text.chars();
+                        chars.next().filter(|_| chars.next().is_none())
+                    }
+                    Entry::Item(_) | Entry::Random | Entry::Empty => None,
+                };
+                if let Some(character) = character {
+                    self.open_reference(character);
+                }
+                rofi_mode::Action::Reload
+            }
+            (config::KeyAction::CopyName, Some(selected)) => match self.entry(selected) {
+                Entry::Item(
+                    item @ Item {
+                        content: Content::Text(_),
+                        ..
+                    },
+                )
+                | Entry::Decoded(_, Some(item)) => self.copy(character_name(&*item.name), None),
+                Entry::Item(_) | Entry::Decoded(_, None) | Entry::Random | Entry::Empty => {
+                    rofi_mode::Action::Reload
+                }
+            },
+            (_, None) => rofi_mode::Action::Reload,
+        }
+    }
+}
+
+impl Unicode {
+    /// Write the index and path of the active list to the file at `$ROFI_UNICODE_STATE`.
+    fn write_state(&self) {
+        let path = match env::var_os("ROFI_UNICODE_STATE") {
+            Some(path) => path,
+            None => {
+                log!("not writing the state, as $ROFI_UNICODE_STATE is not set");
+                return;
+            }
+        };
+
+        let mut names = Vec::new();
+        let mut index = self.active_list().index;
+        while let Some(item_index) = index {
+            names.push(&*self.item(item_index).name);
+            index = self.lists[item_index.list].index;
+        }
+        names.reverse();
+
+        let state = format!("{}\n{}\n", self.active_list, names.join(" / "));
+        if let Err(e) = fs::write(&path, state) {
+            log!(
+                "failed to write the state to {}: {e}",
+                path.to_string_lossy()
+            );
+        }
+    }
+}
+
+impl Unicode {
+    /// Copy text to the clipboard, then do whatever `after_copy` says if it succeeds.
+    fn copy(&self, text: &str, template: Option<&config::CopyTemplate>) -> rofi_mode::Action {
+        let payload = self.payload(text, template);
+        log!("copying {} code points", payload.chars().count());
+        match self.clipboard.copy(&*payload) {
+            Ok(()) => {}
+            Err(e) if self.stdout_fallback && e.is_unavailable() => {
+                log!("no clipboard tool is available ({e}), so printing the text instead");
+                let mut stdout = io::stdout();
+                if let Err(e) = stdout
+                    .write_all(payload.as_bytes())
+                    .and_then(|()| stdout.flush())
+                {
+                    log!("failed to print text: {e}");
+                    return rofi_mode::Action::Reload;
+                }
+            }
+            Err(e) => {
+                log!("failed to copy text to clipboard: {e}");
+                return rofi_mode::Action::Reload;
+            }
+        }
+        match self.after_copy {
+            config::AfterCopy::Exit => rofi_mode::Action::Exit,
+            config::AfterCopy::Reload => rofi_mode::Action::Reload,
+            config::AfterCopy::NextMode => rofi_mode::Action::Next,
+        }
+    }
+
+    /// Put text through everything it goes through before it is copied.
+    fn payload(&self, text: &str, template: Option<&config::CopyTemplate>) -> String {
+        let trimmed = text.trim_matches(|c: char| c.is_ascii_whitespace());
+        // Items that copy whitespace itself are left alone.
+        let text = if self.trim_copy && !trimmed.is_empty() {
+            trimmed
+        } else {
+            text
+        };
+        let text = match template {
+            Some(template) => template.apply(text),
+            None => text.to_owned(),
+        };
+
+        let mut payload = match self.copy_normalization {
+            config::Normalization::None => text,
+            config::Normalization::Nfc => text.nfc().collect(),
+            config::Normalization::Nfd => text.nfd().collect(),
+            config::Normalization::Nfkc => text.nfkc().collect(),
+            config::Normalization::Nfkd => text.nfkd().collect(),
+        };
+        if self.append_newline {
+            payload.push('\n');
+        }
+        payload
+    }
+}
+
+/// The name of the character an item copies, without markup.
+///
+/// For items generated from the Unicode data,
+/// whose names are written as `U+XXXX<tab>glyph<tab>NAME (aliases)`,
+/// this is just `NAME`; Unicode's names never contain parentheses,
+/// so the alias section is unambiguous.
+/// For any other item, it is the item's whole name.
+fn character_name(name: &str) -> &str {
+    let mut fields = name.splitn(3, '\t');
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some(code_point), Some(_glyph), Some(rest)) if code_point.starts_with("U+") => {
+            rest.split(" (").next().unwrap()
+        }
+        _ => name,
+    }
+}
+
+/// Write text as a Bash `$'...'` string of escaped code points, such as `$'\u2764'` for ❤.
+fn shell_escape(text: &str) -> String {
+    let mut escaped = String::from("$'");
+    for c in text.chars() {
+        let code_point = u32::from(c);
+        if code_point <= 0xFFFF {
+            escaped.push_str(&*format!("\\u{code_point:04X}"));
+        } else {
+            escaped.push_str(&*format!("\\U{code_point:08X}"));
+        }
+    }
+    escaped.push('\'');
+    escaped
+}
+
+/// Whether text consists only of characters that are invisible when pasted,
+/// but can change how the text around them behaves.
+fn is_invisible(text: &str) -> bool {
+    const INVISIBLE: &[RangeInclusive<u32>] = &[
+        0x00AD..=0x00AD,
+        0x034F..=0x034F,
+        0x061C..=0x061C,
+        0x115F..=0x1160,
+        0x17B4..=0x17B5,
+        0x180B..=0x180F,
+        0x200B..=0x200F,
+        0x202A..=0x202E,
+        0x2060..=0x2064,
+        0x2066..=0x206F,
+        0x3164..=0x3164,
+        0xFE00..=0xFE0F,
+        0xFEFF..=0xFEFF,
+        0xFFA0..=0xFFA0,
+        0xE0000..=0xE0FFF,
+    ];
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| INVISIBLE.iter().any(|range| range.contains(&u32::from(c))))
+}
+
+/// Parse a code point written as `u+X` or as a bare `XXXX` of at least four hex digits,
+/// so that short words like `bad` aren't mistaken for code points.
+fn parse_code_point(input: &str) -> Option<char> {
+    let input = input.trim();
+    let (hex, min_len) = match input.strip_prefix("u+") {
+        Some(hex) => (hex, 1),
+        None => (input, 4),
+    };
+    if !(min_len..=6).contains(&hex.len()) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Parse an inclusive range of code points written as `XXXX..YYYY`, or `XXXX..` for no end,
+/// with each code point optionally prefixed by `u+`.
+fn parse_range(input: &str) -> Option<RangeInclusive<u32>> {
+    fn code_point(s: &str) -> Option<u32> {
+        let hex = s.strip_prefix("u+").unwrap_or(s);
+        if !(1..=6).contains(&hex.len()) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(hex, 16).ok()
+    }
+
+    let (start, end) = input.trim().split_once("..")?;
+    let start = code_point(start)?;
+    let end = if end.is_empty() {
+        u32::from(char::MAX)
+    } else {
+        code_point(end)?
+    };
+    Some(start..=end)
+}
+
+fn log_line(args: fmt::Arguments<'_>) {
+    eprintln!("{args}");
+
+    let path = match env::var_os("ROFI_UNICODE_LOG") {
+        Some(path) => path,
+        None => return,
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    // Failing to log is not worth reporting anywhere.
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "[{timestamp}] {args}");
     }
 }
 
-fn clipboard_copy(text: &str) -> anyhow::Result<()> {
-    let mut child = process::Command::new("xclip")
-        .arg("-selection")
-        .arg("clipboard")
-        .arg("-quiet")
-        .current_dir("/")
-        .stdin(process::Stdio::piped())
-        .stdout(process::Stdio::null())
-        .stderr(process::Stdio::null())
-        .spawn()
-        .context("failed to spawn xclip")?;
-    child.stdin.take().unwrap().write_all(text.as_bytes())?;
-    Ok(())
+/// Generate a random index less than `len`, seeded from the current time.
+fn random_index(len: usize) -> usize {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64);
+
+    // SplitMix64, which is plenty for a single value
+    let mut z = nanos.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    (z % len as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build the mode from the contents of a `config.ron`, as Rofi would initialize it.
+    fn unicode(config_ron: &str) -> Unicode {
+        Unicode::from_config(config::from_str(config_ron).unwrap()).unwrap()
+    }
+
+    impl Unicode {
+        /// Send an event with the given input, as Rofi would,
+        /// returning the resulting action and input.
+        fn send(&mut self, event: rofi_mode::Event, input: &str) -> (rofi_mode::Action, String) {
+            let mut input = rofi_mode::String::from(input);
+            let action = self.react(event, &mut input);
+            (action, String::from(&*input))
+        }
+
+        /// Select a line, as by pressing Enter.
+        fn select(&mut self, line: usize) -> rofi_mode::Action {
+            let event = rofi_mode::Event::Ok {
+                alt: false,
+                selected: line,
+            };
+            self.send(event, "").0
+        }
+
+        /// Cancel, as by pressing Escape.
+        fn cancel(&mut self) -> rofi_mode::Action {
+            self.send(rofi_mode::Event::Cancel { selected: None }, "").0
+        }
+
+        /// The content of every line shown.
+        fn lines(&mut self) -> Vec<String> {
+            (0..self.entries())
+                .map(|line| String::from(&*self.entry_content(line)))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn enter_and_leave_submenu() {
+        let mut unicode = unicode(
+            r#"Config(root: {
+                "Heart": "❤",
+                "Arrows": { "Left": "←", "Right": "→" },
+            })"#,
+        );
+        assert_eq!(unicode.lines(), ["Heart", "Arrows/"]);
+
+        assert_eq!(unicode.select(1), rofi_mode::Action::Reload);
+        assert_ne!(unicode.active_list, 0);
+        assert_eq!(unicode.lines(), ["Left", "Right"]);
+
+        assert_eq!(unicode.cancel(), rofi_mode::Action::Reload);
+        assert_eq!(unicode.active_list, 0);
+        assert_eq!(unicode.lines(), ["Heart", "Arrows/"]);
+
+        assert_eq!(unicode.cancel(), rofi_mode::Action::Exit);
+    }
+
+    #[test]
+    fn completion() {
+        let unicode = unicode(r#"Config(root: { "Heart": "❤", "Arrows": { "Left": "←" } })"#);
+        assert_eq!(&*unicode.completed(0), "Heart");
+        assert_eq!(&*unicode.completed(1), "Arrows/");
+
+        // Completing the completed name again enters the submenu.
+        let mut unicode = unicode;
+        let complete = || rofi_mode::Event::Complete { selected: Some(1) };
+        assert_eq!(unicode.send(complete(), "arr").1, "Arrows/");
+        assert_eq!(unicode.send(complete(), "Arrows/").1, "");
+        assert_eq!(unicode.lines(), ["Left"]);
+    }
+
+    #[test]
+    fn append_newline() {
+        let unicode_without = unicode("Config(root: {})");
+        assert_eq!(unicode_without.payload("❤", None), "❤");
+
+        let unicode_with = unicode("Config(root: {}, append_newline: true)");
+        assert_eq!(unicode_with.payload("❤", None), "❤\n");
+    }
+
+    #[test]
+    fn normalization() {
+        let nfd = unicode("Config(root: {}, copy_normalization: Nfd)");
+        assert_eq!(nfd.payload("\u{E9}", None), "e\u{301}");
+
+        let nfc = unicode("Config(root: {}, copy_normalization: Nfc)");
+        assert_eq!(nfc.payload("e\u{301}", None), "\u{E9}");
+
+        let none = unicode("Config(root: {})");
+        assert_eq!(none.payload("e\u{301}", None), "e\u{301}");
+    }
+
+    #[test]
+    fn trim_copy() {
+        let unicode = unicode("Config(root: {}, trim_copy: true)");
+        assert_eq!(unicode.payload(" (╯°□°)╯ \n", None), "(╯°□°)╯");
+        // Text that is only whitespace is kept as it is.
+        assert_eq!(unicode.payload(" ", None), " ");
+        assert_eq!(unicode.payload("\t\n", None), "\t\n");
+        // Only ASCII whitespace is trimmed.
+        assert_eq!(
+            unicode.payload("\u{3000}x\u{3000}", None),
+            "\u{3000}x\u{3000}"
+        );
+    }
+
+    #[test]
+    fn copy_template() {
+        let unicode = unicode(
+            r#"Config(root: {
+                "HTML": {
+                    "copy_template": "&#x{:x};",
+                    "Hearts": { "Heart": "❤" },
+                },
+                "Quoted": { "copy_template": "'{}'", "Heart": "❤" },
+            })"#,
+        );
+        // Inherited from the parent list
+        let hearts = &unicode.lists[2];
+        assert_eq!(
+            unicode.payload("❤", hearts.copy_template.as_ref()),
+            "&#x2764;"
+        );
+        let quoted = &unicode.lists[3];
+        assert_eq!(unicode.payload("❤", quoted.copy_template.as_ref()), "'❤'");
+        assert!(unicode.lists[0].copy_template.is_none());
+    }
+
+    #[test]
+    fn stdout_fallback() {
+        let config_ron = |stdout_fallback| {
+            format!(
+                r#"Config(
+                    root: {{ "Heart": "❤" }},
+                    clipboard_command: Some(["rofi-unicode-test-nonexistent-program", "{{}}"]),
+                    stdout_fallback: {},
+                )"#,
+                stdout_fallback,
+            )
+        };
+
+        // Printed instead, which counts as copying it
+        let unicode_with = unicode(&config_ron(true));
+        assert_eq!(unicode_with.copy("❤", None), rofi_mode::Action::Exit);
+
+        let unicode_without = unicode(&config_ron(false));
+        assert_eq!(unicode_without.copy("❤", None), rofi_mode::Action::Reload);
+    }
+
+    #[test]
+    fn cancel_behavior() {
+        let config_ron = |cancel_behavior| {
+            format!(
+                r#"Config(
+                    root: {{ "Arrows": {{ "Left": {{ "Heavy": "🡄" }}, "Right": "→" }} }},
+                    cancel_behavior: {},
+                )"#,
+                cancel_behavior,
+            )
+        };
+
+        let mut up = unicode(&config_ron("Up"));
+        up.select(0);
+        up.select(0);
+        assert_eq!(up.lines(), ["Heavy"]);
+        assert_eq!(up.cancel(), rofi_mode::Action::Reload);
+        assert_eq!(up.lines(), ["Left/", "Right"]);
+        assert_eq!(up.cancel(), rofi_mode::Action::Reload);
+        assert_eq!(up.lines(), ["Arrows/"]);
+        assert_eq!(up.cancel(), rofi_mode::Action::Exit);
+
+        let mut exit = unicode(&config_ron("Exit"));
+        exit.select(0);
+        exit.select(0);
+        assert_eq!(exit.lines(), ["Heavy"]);
+        assert_eq!(exit.cancel(), rofi_mode::Action::Exit);
+    }
+
+    #[test]
+    fn max_results() {
+        let config_ron = |match_mode| {
+            format!(
+                r#"Config(
+                    root: {{
+                        "match_mode": {},
+                        "Left arrow": "←",
+                        "Heart": "❤",
+                        "Up arrow": "↑",
+                        "Right arrow": "→",
+                        "Down arrow": "↓",
+                    }},
+                    max_results: Some(2),
+                )"#,
+                match_mode,
+            )
+        };
+        let mut unicode = unicode(&config_ron("Substring"));
+        unicode.preprocess_input("ARROW");
+        // Only the first two arrows are shown.
+        assert_eq!(unicode.result_limit, Some(3));
+
+        unicode.preprocess_input("heart");
+        assert_eq!(unicode.result_limit, None);
+
+        // Not limited when not searching
+        unicode.preprocess_input("");
+        assert_eq!(unicode.result_limit, None);
+
+        // Rofi's own matching is left alone.
+        let mut fuzzy = self::unicode(&config_ron("Fuzzy"));
+        fuzzy.preprocess_input("arrow");
+        assert_eq!(fuzzy.result_limit, None);
+        let item = &fuzzy.active_list().items[0];
+        assert_eq!(fuzzy.item_matches(item), None);
+    }
+
+    #[test]
+    fn glyphs_display() {
+        let mut unicode = unicode(
+            r#"Config(root: {
+                "display": Glyphs,
+                "match_mode": Substring,
+                "LATIN SMALL LETTER A WITH ACUTE": "á",
+                "Arrows": { "Left": "←" },
+            })"#,
+        );
+        // Only text items are shown without their name.
+        assert_eq!(unicode.lines(), ["á", "Arrows/"]);
+
+        // But they are still found by it.
+        unicode.preprocess_input("acute");
+        let item = &unicode.active_list().items[0];
+        assert_eq!(unicode.item_matches(item), Some(true));
+
+        unicode.preprocess_input("grave");
+        let item = &unicode.active_list().items[0];
+        assert_eq!(unicode.item_matches(item), Some(false));
+    }
+
+    #[test]
+    fn character_names() {
+        // As generated by `cargo dev generate`
+        let unicode = unicode(
+            r#"Config(root: {
+                "U+00E1\tá\tLATIN SMALL LETTER A WITH ACUTE (<small>a acute</small>)": "á",
+                "U+0041\tA\tLATIN CAPITAL LETTER A": "A",
+                "Shrug (<i>kaomoji</i>)": "¯\\_(ツ)_/¯",
+            })"#,
+        );
+        let names = unicode.lists[0]
+            .items
+            .iter()
+            .map(|item| character_name(&*item.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "LATIN SMALL LETTER A WITH ACUTE",
+                "LATIN CAPITAL LETTER A",
+                "Shrug (kaomoji)",
+            ],
+        );
+    }
+
+    #[test]
+    fn shell_escapes() {
+        assert_eq!(shell_escape("❤"), "$'\\u2764'");
+        assert_eq!(shell_escape("a'"), "$'\\u0061\\u0027'");
+        // Beyond the Basic Multilingual Plane
+        assert_eq!(shell_escape("😀"), "$'\\U0001F600'");
+        assert_eq!(shell_escape(""), "$''");
+    }
 }
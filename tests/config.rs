@@ -0,0 +1,54 @@
+use ::{
+    rofi_unicode::config::{self, Content, List},
+    std::path::PathBuf,
+};
+
+fn fixture(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect()
+}
+
+/// Describe a list as the name of each item, followed by the names of its items if it is a submenu.
+fn shape(list: &List) -> Vec<(String, Vec<String>)> {
+    list.items
+        .iter()
+        .map(|item| {
+            let nested = match &item.content {
+                Content::Items(nested) => {
+                    nested.items.iter().map(|item| item.name.clone()).collect()
+                }
+                Content::Text(_) | Content::Separator => Vec::new(),
+            };
+            (item.name.clone(), nested)
+        })
+        .collect()
+}
+
+#[test]
+fn tree() {
+    let (config, _) = config::read_timed(vec![fixture("tree")], "config.ron").unwrap();
+
+    let expected = [
+        ("Arrows", &["↔", "←", "→"][..]),
+        ("Heart", &[]),
+        ("Spaces", &["No-break space"]),
+    ];
+    let expected = expected
+        .iter()
+        .map(|(name, nested)| {
+            let nested = nested.iter().map(|&name| name.to_owned()).collect();
+            (name.to_string(), nested)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(shape(&config.root), expected);
+
+    let spaces = match &config.root.items[2].content {
+        Content::Items(spaces) => spaces,
+        _ => panic!("not a submenu"),
+    };
+    let space = &spaces.items[0];
+    assert_eq!(space.name_markup, "<b>No-break space</b>");
+    assert_eq!(space.name_attributes.len(), 1);
+    assert!(matches!(&space.content, Content::Text(text) if text == "\u{A0}"));
+}